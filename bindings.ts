@@ -8,7 +8,7 @@ export const commands = {
 async errorPopup(msg: string) : Promise<void> {
     await TAURI_INVOKE("error_popup", { msg });
 },
-async getSettings() : Promise<Result<Settings, string>> {
+async getSettings() : Promise<Result<Settings, KadiumError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("get_settings") };
 } catch (e) {
@@ -16,7 +16,15 @@ async getSettings() : Promise<Result<Settings, string>> {
     else return { status: "error", error: e  as any };
 }
 },
-async tags() : Promise<Result<string[], string>> {
+async getChannels() : Promise<Result<Channel[], KadiumError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_channels") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async tags() : Promise<Result<string[], KadiumError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("tags") };
 } catch (e) {
@@ -24,7 +32,7 @@ async tags() : Promise<Result<string[], string>> {
     else return { status: "error", error: e  as any };
 }
 },
-async setChannels(channels: Channel[]) : Promise<Result<null, string>> {
+async setChannels(channels: Channel[]) : Promise<Result<null, KadiumError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("set_channels", { channels }) };
 } catch (e) {
@@ -32,7 +40,7 @@ async setChannels(channels: Channel[]) : Promise<Result<null, string>> {
     else return { status: "error", error: e  as any };
 }
 },
-async addChannel(options: AddChannelOptions) : Promise<Result<null, string>> {
+async addChannel(options: AddChannelOptions) : Promise<Result<null, KadiumError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("add_channel", { options }) };
 } catch (e) {
@@ -40,7 +48,7 @@ async addChannel(options: AddChannelOptions) : Promise<Result<null, string>> {
     else return { status: "error", error: e  as any };
 }
 },
-async setGeneralSettings(apiKey: string, maxConcurrentRequests: number, checkInBackground: boolean, noWindowDecorations: boolean) : Promise<Result<null, string>> {
+async setGeneralSettings(apiKey: string, maxConcurrentRequests: number, checkInBackground: boolean, noWindowDecorations: boolean) : Promise<Result<null, KadiumError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("set_general_settings", { apiKey, maxConcurrentRequests, checkInBackground, noWindowDecorations }) };
 } catch (e) {
@@ -48,7 +56,7 @@ async setGeneralSettings(apiKey: string, maxConcurrentRequests: number, checkInB
     else return { status: "error", error: e  as any };
 }
 },
-async checkNow() : Promise<Result<null, string>> {
+async checkNow() : Promise<Result<null, KadiumError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("check_now") };
 } catch (e) {
@@ -56,15 +64,41 @@ async checkNow() : Promise<Result<null, string>> {
     else return { status: "error", error: e  as any };
 }
 },
-async getHistory() : Promise<Result<UndoHistory, string>> {
+/**
+ * Newest entries first. Pass the ID of the last entry as `before_id` to get
+ * the next page
+ */
+async getHistory(limit: number, beforeId: number | null) : Promise<Result<HistoryEntry[], KadiumError>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("get_history") };
+    return { status: "ok", data: await TAURI_INVOKE("get_history", { limit, beforeId }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
 }
 },
-async getVideos(options: Options, after: After | null) : Promise<Result<Video[], string>> {
+/**
+ * Reverts the most recent undoable action. Returns the undone action
+ */
+async undo() : Promise<Result<Action | null, KadiumError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("undo") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Performs the most recently undone action again. Returns the redone action
+ */
+async redo() : Promise<Result<Action | null, KadiumError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("redo") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getVideos(options: Options, after: After | null) : Promise<Result<Video[], KadiumError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("get_videos", { options, after }) };
 } catch (e) {
@@ -72,7 +106,7 @@ async getVideos(options: Options, after: After | null) : Promise<Result<Video[],
     else return { status: "error", error: e  as any };
 }
 },
-async archive(id: string) : Promise<Result<null, string>> {
+async archive(id: string) : Promise<Result<null, KadiumError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("archive", { id }) };
 } catch (e) {
@@ -80,13 +114,83 @@ async archive(id: string) : Promise<Result<null, string>> {
     else return { status: "error", error: e  as any };
 }
 },
-async unarchive(id: string) : Promise<Result<null, string>> {
+async unarchive(id: string) : Promise<Result<null, KadiumError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("unarchive", { id }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
 }
+},
+/**
+ * Returns how many videos were archived
+ */
+async archiveMany(ids: string[]) : Promise<Result<number, KadiumError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("archive_many", { ids }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Returns how many videos were unarchived
+ */
+async unarchiveMany(ids: string[]) : Promise<Result<number, KadiumError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("unarchive_many", { ids }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Archives every video `get_videos` would return with these options,
+ * ignoring the limit. Returns how many videos were archived
+ */
+async archiveMatching(options: Options) : Promise<Result<number, KadiumError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("archive_matching", { options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async markRead(id: string) : Promise<Result<null, KadiumError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("mark_read", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async markUnread(id: string) : Promise<Result<null, KadiumError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("mark_unread", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Marks every unread video as read, or only the ones from a channel or tag.
+ * The history entry lists the affected videos so it can be reverted.
+ */
+async markAllRead(channelId: string | null, tag: string | null) : Promise<Result<null, KadiumError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("mark_all_read", { channelId, tag }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getQuotaUsage() : Promise<Result<QuotaUsage, KadiumError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_quota_usage") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
 }
 }
 
@@ -100,18 +204,240 @@ async unarchive(id: string) : Promise<Result<null, string>> {
 
 /** user-defined types **/
 
-export type Action = "CheckNow" | { Archive: string } | { Unarchive: string } | { AddChannel: string } | { UpdateOrDeleteChannels: string }
+export type Action = "CheckNow" | { Archive: string } | { Unarchive: string } | { 
+/**
+ * IDs of the videos that were archived
+ */
+ArchiveMany: string[] } | { 
+/**
+ * IDs of the videos that were unarchived
+ */
+UnarchiveMany: string[] } | { MarkRead: string } | { MarkUnread: string } | { 
+/**
+ * IDs of the videos that were marked as read
+ */
+MarkAllRead: string[] } | { 
+/**
+ * IDs of the videos archived by archive rules
+ */
+AutoArchive: string[] } | { AddChannel: Channel } | { UpdateOrDeleteChannels: { previous: Channel[]; new: Channel[] } }
 export type AddChannelOptions = { url: string; from_time: number; refresh_rate_ms: number; tags: string[] }
 export type After = { publishTimeMs: number; id: string }
-export type Channel = { id: string; name: string; icon: string; uploads_playlist_id: string; from_time: number; refresh_rate_ms: number; tags: string[] }
-export type Options = { show_all: boolean; show_archived: boolean; channel_filter: string; tag: string | null; limit: number }
-export type Settings = { api_key: string; max_concurrent_requests: number; channels: Channel[]; check_in_background: boolean; no_window_decorations?: boolean }
-export type UndoHistory = { entries: ([number, Action])[] }
+/**
+ * Videos matching any rule are archived automatically, unless they were unarchived
+ */
+export type ArchiveRules = { 
+/**
+ * Archive videos published more than this many days ago
+ */
+older_than_days?: number | null; 
+/**
+ * Archive videos shorter than this many seconds
+ */
+shorter_than_secs?: number | null; 
+/**
+ * Archive livestreams and premieres once they have ended
+ */
+live_replays?: boolean }
+/**
+ * Where to look for new uploads
+ */
+export type Backend = 
+/**
+ * The channel's uploads playlist. Costs quota, but sees every upload
+ */
+"DataApi" | 
+/**
+ * The channel's Atom feed. Costs no quota, but only sees the latest 15 uploads
+ */
+"Feed"
+export type Channel = { id: string; name: string; icon: string; uploads_playlist_id: string; from_time: number; refresh_rate_ms: number; tags: string[]; 
+/**
+ * Overrides the global backend
+ */
+backend: Backend | null; archive_rules: ArchiveRules; 
+/**
+ * Notify this many minutes before livestreams and premieres start
+ */
+remind_minutes_before: number | null; 
+/**
+ * Skip Shorts instead of saving them
+ */
+ignore_shorts: boolean; title_filter: TitleFilter; 
+/**
+ * Notify about new uploads. Overrides the tags and the global setting
+ */
+notify_new_uploads: boolean | null }
+/**
+ * Lets the frontend react differently depending on what went wrong
+ */
+export type ErrorKind = 
+/**
+ * The daily quota of the API key is used up
+ */
+"QuotaExceeded" | "RateLimited" | 
+/**
+ * The API key is invalid or not allowed to use the API
+ */
+"ApiKey" | "NotFound" | "Network" | 
+/**
+ * YouTube returned something we didn't expect
+ */
+"Parse" | 
+/**
+ * YouTube had an internal error
+ */
+"Server" | 
+/**
+ * Other errors returned by the YouTube API
+ */
+"Api" | "ChannelExists" | "InvalidUrl" | "Database" | "File" | "Other"
+export type HistoryEntry = { id: number; 
+/**
+ * Unix time in seconds
+ */
+time: number; action: Action; undone: boolean }
+export type KadiumError = { kind: ErrorKind; 
+/**
+ * What we were doing when the error happened, like "Failed to get channel"
+ */
+context: string | null; message: string }
+/**
+ * Livestreams and premieres. Upcoming and live ones are checked again until
+ * they end
+ */
+export type LiveState = 
+/**
+ * A regular upload
+ */
+"None" | "Upcoming" | "Live" | 
+/**
+ * The livestream or premiere has ended
+ */
+"Replay"
+export type NotificationClick = 
+/**
+ * Open the video on YouTube
+ */
+"OpenVideo" | 
+/**
+ * Show Kadium with the video selected
+ */
+"ShowApp"
+/**
+ * When new video notifications are held back and delivered as a digest
+ */
+export type NotificationSchedule = { quiet_hours?: QuietHours | null; 
+/**
+ * Times of day like "08:00". If any are set, new video notifications are
+ * only delivered at these times
+ */
+digest_times?: string[] }
+export type Options = { show_all: boolean; show_archived: boolean; channel_filter: string; tag: string | null; unread_only?: boolean; 
+/**
+ * Searches titles and descriptions. Supports `"phrases"`, `prefix*`,
+ * `channel:name` and `tag:name`
+ */
+query?: string; min_duration_ms?: number | null; max_duration_ms?: number | null; published_after_ms?: number | null; published_before_ms?: number | null; 
+/**
+ * Hides videos detected as Shorts when they were saved
+ */
+hide_shorts?: boolean; only_shorts?: boolean; 
+/**
+ * Only livestreams and premieres that have ended
+ */
+only_live_replays?: boolean; limit: number }
+/**
+ * Notifications are queued from `start` until `end`, like "22:00" until
+ * "07:00", and delivered together afterwards
+ */
+export type QuietHours = { start: string; end: string; 
+/**
+ * The days the quiet hours start on, like "Mon". Every day if empty
+ */
+weekdays?: string[] }
+export type QuotaUsage = { 
+/**
+ * Units used since the last reset
+ */
+used: number; 
+/**
+ * Units background checks are expected to use per day
+ */
+projected: number; limit: number; resets_at_ms: number }
+export type Settings = { api_key: string; max_concurrent_requests: number; check_in_background: boolean; no_window_decorations: boolean; 
+/**
+ * How many pages of 50 uploads to fetch per channel check at most
+ */
+max_playlist_pages: number; backend: Backend; 
+/**
+ * For pointing Kadium at a caching proxy or mock server
+ */
+api_base_url: string | null; 
+/**
+ * Like `api_base_url`, but for the Atom feeds
+ */
+feed_base_url: string | null; connect_timeout_ms: number; 
+/**
+ * Covers the whole request, including reading the response
+ */
+request_timeout_ms: number; 
+/**
+ * HTTP, HTTPS or SOCKS5 proxy URL, like socks5://127.0.0.1:1080
+ */
+proxy: string | null; 
+/**
+ * How many history entries to keep
+ */
+history_retention: number; 
+/**
+ * Applies to every channel with the tag
+ */
+tag_archive_rules: { [key in string]: ArchiveRules }; notify_new_uploads: boolean; 
+/**
+ * Overrides the global setting for every channel with the tag. If the
+ * channel's tags disagree, notifications are on
+ */
+tag_notify_new_uploads: { [key in string]: boolean }; notification_schedule: NotificationSchedule; 
+/**
+ * What clicking a new video notification does
+ */
+notification_click: NotificationClick; 
+/**
+ * Archive videos that are opened from a notification
+ */
+archive_on_open: boolean }
+/**
+ * Decides which new uploads are saved as usual, based on their title
+ */
+export type TitleFilter = { 
+/**
+ * If not empty, titles must match one of these
+ */
+include?: TitlePattern[]; 
+/**
+ * Titles matching any of these are filtered out
+ */
+exclude?: TitlePattern[]; 
+/**
+ * Save filtered out videos as archived instead of skipping them
+ */
+archive_filtered?: boolean }
+export type TitlePattern = { pattern: string; 
+/**
+ * Substrings match case-insensitively. Regexes are case-sensitive unless
+ * they start with `(?i)`
+ */
+regex?: boolean }
 export type Video = { id: string; title: string; description: string; publishTimeMs: number; 
 /**
  * SQLite does not support unsigned integers
  */
-durationMs: number; thumbnailStandard: boolean; thumbnailMaxres: boolean; channelId: string; channelName: string; unread: boolean; archived: boolean }
+durationMs: number; thumbnailStandard: boolean; thumbnailMaxres: boolean; channelId: string; channelName: string; unread: boolean; archived: boolean; liveState: LiveState; 
+/**
+ * Only known for livestreams and premieres
+ */
+scheduledStartMs: number | null; isShort: boolean }
 
 /** tauri-specta globals **/
 
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS quotaUsage
+(
+    day               TEXT    NOT NULL PRIMARY KEY,
+    units             INTEGER NOT NULL DEFAULT 0
+);
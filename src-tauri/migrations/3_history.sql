@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS history
+(
+    id                INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    time              INTEGER NOT NULL,
+    action            TEXT    NOT NULL,
+    undone            BOOLEAN NOT NULL DEFAULT 0
+);
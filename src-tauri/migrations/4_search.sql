@@ -0,0 +1,27 @@
+CREATE VIRTUAL TABLE IF NOT EXISTS videosSearch USING fts5
+(
+    id UNINDEXED,
+    title,
+    description
+);
+
+INSERT INTO videosSearch (id, title, description)
+SELECT id, title, description FROM videos;
+
+CREATE TRIGGER IF NOT EXISTS videosSearchInsert AFTER INSERT ON videos
+BEGIN
+    INSERT INTO videosSearch (id, title, description)
+    VALUES (new.id, new.title, new.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS videosSearchDelete AFTER DELETE ON videos
+BEGIN
+    DELETE FROM videosSearch WHERE id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS videosSearchUpdate AFTER UPDATE OF id, title, description ON videos
+BEGIN
+    DELETE FROM videosSearch WHERE id = old.id;
+    INSERT INTO videosSearch (id, title, description)
+    VALUES (new.id, new.title, new.description);
+END;
@@ -0,0 +1,2 @@
+ALTER TABLE videos ADD COLUMN liveState TEXT NOT NULL DEFAULT 'None';
+ALTER TABLE videos ADD COLUMN scheduledStartMs INTEGER;
@@ -0,0 +1,12 @@
+ALTER TABLE videos ADD COLUMN isShort BOOLEAN NOT NULL DEFAULT 0;
+
+-- Existing videos have no aspect ratio saved, so only the original Shorts
+-- length is used
+UPDATE videos SET isShort = 1
+WHERE durationMs > 0 AND durationMs <= 60000 AND liveState = 'None';
+
+-- Uploads that were skipped, so they aren't fetched again on every check
+CREATE TABLE IF NOT EXISTS ignoredVideos
+(
+    id TEXT NOT NULL PRIMARY KEY
+);
@@ -0,0 +1,85 @@
+CREATE TABLE IF NOT EXISTS channels
+(
+    id                TEXT    NOT NULL PRIMARY KEY,
+    name              TEXT    NOT NULL,
+    icon              TEXT    NOT NULL,
+    uploadsPlaylistId TEXT    NOT NULL,
+    fromTime          INTEGER NOT NULL,
+    refreshRateMs     INTEGER NOT NULL,
+    -- Order in the channel list. NULL for channels that were removed, which
+    -- are kept because their videos refer to them
+    position          INTEGER,
+    -- The remaining channel options as JSON
+    options           TEXT    NOT NULL DEFAULT '{}'
+);
+
+CREATE TABLE IF NOT EXISTS channelTags
+(
+    channelId TEXT    NOT NULL REFERENCES channels (id) ON DELETE CASCADE,
+    tag       TEXT    NOT NULL,
+    -- Order in the channel's tags
+    position  INTEGER NOT NULL,
+    PRIMARY KEY (channelId, tag)
+);
+CREATE INDEX IF NOT EXISTS channelTagsTag ON channelTags (tag);
+
+-- Every channel with videos gets a row, so videos can refer to it. The
+-- channels from Settings.json are imported on startup
+INSERT INTO channels (id, name, icon, uploadsPlaylistId, fromTime, refreshRateMs)
+SELECT DISTINCT channelId,
+    (SELECT channelName FROM videos AS latest
+     WHERE latest.channelId = videos.channelId
+     ORDER BY publishTimeMs DESC LIMIT 1),
+    '', '', 0, 0
+FROM videos;
+
+-- SQLite can't add a foreign key to an existing table, so it's recreated.
+-- The channel name is now read from the channels table
+CREATE TABLE videosNew
+(
+    id                TEXT    NOT NULL PRIMARY KEY,
+    title             TEXT    NOT NULL,
+    description       TEXT    NOT NULL,
+    publishTimeMs     INTEGER NOT NULL,
+    durationMs        INTEGER NOT NULL,
+    thumbnailStandard BOOLEAN NOT NULL,
+    thumbnailMaxres   BOOLEAN NOT NULL,
+    channelId         TEXT    NOT NULL REFERENCES channels (id),
+    unread            BOOLEAN NOT NULL DEFAULT 1,
+    archived          BOOLEAN NOT NULL DEFAULT 0,
+    liveState         TEXT    NOT NULL DEFAULT 'None',
+    scheduledStartMs  INTEGER,
+    isShort           BOOLEAN NOT NULL DEFAULT 0
+);
+INSERT INTO videosNew (id, title, description, publishTimeMs, durationMs, thumbnailStandard,
+    thumbnailMaxres, channelId, unread, archived, liveState, scheduledStartMs, isShort)
+SELECT id, title, description, publishTimeMs, durationMs, thumbnailStandard,
+    thumbnailMaxres, channelId, unread, archived, liveState, scheduledStartMs, isShort
+FROM videos;
+DROP TABLE videos;
+ALTER TABLE videosNew RENAME TO videos;
+CREATE INDEX IF NOT EXISTS videosChannelId ON videos (channelId);
+
+-- Dropped along with the old table
+CREATE TRIGGER IF NOT EXISTS videosSearchInsert AFTER INSERT ON videos
+BEGIN
+    INSERT INTO videosSearch (id, title, description)
+    VALUES (new.id, new.title, new.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS videosSearchDelete AFTER DELETE ON videos
+BEGIN
+    DELETE FROM videosSearch WHERE id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS videosSearchUpdate AFTER UPDATE OF id, title, description ON videos
+BEGIN
+    DELETE FROM videosSearch WHERE id = old.id;
+    INSERT INTO videosSearch (id, title, description)
+    VALUES (new.id, new.title, new.description);
+END;
+
+CREATE VIEW IF NOT EXISTS videosWithChannel AS
+SELECT videos.*, channels.name AS channelName
+FROM videos
+JOIN channels ON channels.id = videos.channelId;
@@ -0,0 +1,2 @@
+-- Archive rules leave alone videos the user has unarchived
+ALTER TABLE videos ADD COLUMN userUnarchived BOOLEAN NOT NULL DEFAULT 0;
@@ -1,65 +1,227 @@
-use crate::throw;
+use crate::error::{ErrorKind, KadiumError};
+use crate::quota;
+use crate::settings::Settings;
 use serde::de::DeserializeOwned;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
 
-pub async fn yt_request<T: DeserializeOwned>(url: &str, key: &str) -> Result<T, String> {
-	let client = reqwest::Client::new();
-	let json: serde_json::Value = client
-		.get(url)
-		.header("X-Goog-Api-Key", key)
+/// Limits how many requests can be in flight at once, shared between all
+/// background intervals and commands
+#[derive(Clone)]
+pub struct RequestLimiter {
+	semaphore: Arc<Semaphore>,
+	limit: usize,
+}
+impl RequestLimiter {
+	pub fn new(max_concurrent_requests: u32) -> Self {
+		let limit = (max_concurrent_requests as usize).max(1);
+		Self {
+			semaphore: Arc::new(Semaphore::new(limit)),
+			limit,
+		}
+	}
+	pub fn limit(&self) -> usize {
+		self.limit
+	}
+	pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, KadiumError> {
+		match self.semaphore.acquire().await {
+			Ok(permit) => Ok(permit),
+			Err(e) => Err(KadiumError::other(format!("Request limiter closed: {}", e))),
+		}
+	}
+}
+
+/// Everything needed to make YouTube API requests
+#[derive(Clone)]
+pub struct ApiClient {
+	pub key: String,
+	/// Like https://www.googleapis.com/youtube/v3
+	pub base_url: String,
+	/// Like https://www.youtube.com/feeds
+	pub feed_base_url: String,
+	pub limiter: RequestLimiter,
+	/// Used for tracking quota usage
+	pub pool: SqlitePool,
+	/// Shared so connections are reused between requests
+	pub http: reqwest::Client,
+}
+
+fn http_client_builder(settings: &Settings) -> reqwest::ClientBuilder {
+	reqwest::Client::builder()
+		.user_agent(concat!("Kadium/", env!("CARGO_PKG_VERSION")))
+		.connect_timeout(Duration::from_millis(settings.connect_timeout_ms.into()))
+		.timeout(Duration::from_millis(settings.request_timeout_ms.into()))
+}
+
+pub fn http_client(settings: &Settings) -> Result<reqwest::Client, KadiumError> {
+	let mut builder = http_client_builder(settings);
+	match &settings.proxy {
+		Some(proxy_url) if !proxy_url.is_empty() => {
+			let proxy = reqwest::Proxy::all(proxy_url).map_err(|e| {
+				KadiumError::new(ErrorKind::InvalidUrl, e.to_string()).context("Invalid proxy URL")
+			})?;
+			builder = builder.proxy(proxy);
+		}
+		_ => {}
+	}
+	builder
+		.build()
+		.map_err(|e| KadiumError::other(e.to_string()).context("Failed to create HTTP client"))
+}
+
+/// Don't let an invalid proxy setting prevent the app from opening or other
+/// settings from being saved. The proxy can only be fixed in the settings file
+pub fn http_client_or_without_proxy(settings: &Settings) -> reqwest::Client {
+	match http_client(settings) {
+		Ok(http_client) => http_client,
+		Err(e) => {
+			eprintln!("Ignoring proxy: {}", e);
+			http_client_builder(settings)
+				.build()
+				.unwrap_or_else(|_| reqwest::Client::new())
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Endpoint {
+	Channels,
+	PlaylistItems,
+	Videos,
+}
+impl Endpoint {
+	fn path(self) -> &'static str {
+		match self {
+			Endpoint::Channels => "/channels",
+			Endpoint::PlaylistItems => "/playlistItems",
+			Endpoint::Videos => "/videos",
+		}
+	}
+	/// Documentation:
+	/// https://developers.google.com/youtube/v3/determine_quota_cost
+	pub fn quota_cost(self) -> u32 {
+		match self {
+			Endpoint::Channels => 1,
+			Endpoint::PlaylistItems => 1,
+			Endpoint::Videos => 1,
+		}
+	}
+}
+
+/// Documentation:
+/// https://developers.google.com/youtube/v3/docs/errors
+fn error_from_object(error_obj: &serde_json::Value) -> KadiumError {
+	let code = error_obj.get("code").and_then(|v| v.as_i64());
+	let message = error_obj
+		.get("message")
+		.and_then(|v| v.as_str())
+		.unwrap_or_default()
+		.to_string();
+	let reason = error_obj
+		.get("errors")
+		.and_then(|v| v.get(0))
+		.and_then(|v| v.get("reason"))
+		.and_then(|v| v.as_str())
+		.unwrap_or_default();
+	let kind = match reason {
+		"quotaExceeded" | "dailyLimitExceeded" => ErrorKind::QuotaExceeded,
+		"rateLimitExceeded" | "userRateLimitExceeded" => ErrorKind::RateLimited,
+		"keyInvalid" | "keyExpired" => ErrorKind::ApiKey,
+		_ => match code {
+			Some(401) | Some(403) => ErrorKind::ApiKey,
+			Some(404) => ErrorKind::NotFound,
+			Some(429) => ErrorKind::RateLimited,
+			Some(code) if code >= 500 => ErrorKind::Server,
+			_ => ErrorKind::Api,
+		},
+	};
+	let code_str = code.map(|n| n.to_string()).unwrap_or_default();
+	KadiumError::new(kind, format!("{} {}", code_str, message))
+}
+
+/// `query` is appended to the endpoint URL, and should start with `?`
+pub async fn yt_request<T: DeserializeOwned>(
+	endpoint: Endpoint,
+	query: &str,
+	api: &ApiClient,
+) -> Result<T, KadiumError> {
+	let _permit = api.limiter.acquire().await?;
+	let response = api
+		.http
+		.get(api.base_url.trim_end_matches('/').to_string() + endpoint.path() + query)
+		.header("X-Goog-Api-Key", &api.key)
 		.send()
 		.await
-		.map_err(|e| format!("API request failed: {}", e))?
-		.json()
-		.await
-		.map_err(|e| format!("API response was not JSON: {}", e))?;
+		.map_err(|e| KadiumError::new(ErrorKind::Network, e.to_string()))?;
+
+	// Even failed requests cost quota
+	if let Err(e) = quota::add_usage(&api.pool, endpoint.quota_cost()).await {
+		eprintln!("{}", e);
+	}
+
+	let status = response.status();
+	let json: serde_json::Value = match response.json().await {
+		Ok(json) => json,
+		Err(e) if status.is_server_error() => {
+			return Err(KadiumError::new(
+				ErrorKind::Server,
+				format!("{} {}", status.as_u16(), e),
+			));
+		}
+		Err(e) => {
+			return Err(KadiumError::new(
+				ErrorKind::Parse,
+				format!("API response was not JSON: {}", e),
+			));
+		}
+	};
 
 	match json.get("error") {
 		Some(error_obj) => {
-			let code = error_obj.get("code").and_then(|v| v.as_i64());
-			let code_str = code.map(|n| n.to_string()).unwrap_or_default();
-			let message = error_obj.get("message").and_then(|v| v.as_str());
 			println!("{:?}", json);
-			throw!("{} {}", code_str, message.unwrap_or_default());
+			return Err(error_from_object(error_obj));
 		}
 		_ => {}
 	}
 	match serde_json::from_value::<T>(json) {
 		Ok(v) => Ok(v),
-		Err(e) => {
-			throw!("Unexpected API response: {}", e);
-		}
+		Err(e) => Err(KadiumError::new(
+			ErrorKind::Parse,
+			format!("Unexpected API response: {}", e),
+		)),
 	}
 }
 
-pub async fn channel_id_from_video_id(id: &str, key: &str) -> Result<String, String> {
-	let url = "https://youtube.googleapis.com/youtube/v3/videos".to_string()
-		+ "?part=snippet"
-		+ "&id="
-		+ id;
-	let videos = yt_request::<videos::Response>(&url, key)
+pub async fn channel_id_from_video_id(id: &str, api: &ApiClient) -> Result<String, KadiumError> {
+	let query = "?part=snippet".to_string() + "&id=" + id;
+	let videos = yt_request::<videos::Response>(Endpoint::Videos, &query, api)
 		.await
-		.map_err(|e| format!("Failed to get video: {}", e))?;
+		.map_err(|e| e.context("Failed to get video"))?;
 	if let Some(video) = videos.items.first() {
 		return Ok(video.snippet.channelId.clone());
 	}
-	Err("No video returned".to_string())
+	Err(KadiumError::new(ErrorKind::NotFound, "No video returned"))
 }
 
-pub async fn channel_id_from_username(username: &str, key: &str) -> Result<String, String> {
-	let url = "https://youtube.googleapis.com/youtube/v3/channels".to_string()
-		+ "?part=contentDetails,id,snippet"
-		+ "&forUsername="
-		+ username;
-	let channels = yt_request::<channels::Response>(&url, key)
+pub async fn channel_id_from_username(
+	username: &str,
+	api: &ApiClient,
+) -> Result<String, KadiumError> {
+	let query = "?part=contentDetails,id,snippet".to_string() + "&forUsername=" + username;
+	let channels = yt_request::<channels::Response>(Endpoint::Channels, &query, api)
 		.await
-		.map_err(|e| format!("Failed to get video: {}", e))?;
+		.map_err(|e| e.context("Failed to get channel"))?;
 	if channels.items.len() > 1 {
-		return Err("YouTube username search returned in multiple channels".to_string());
+		return Err(KadiumError::other(
+			"YouTube username search returned in multiple channels",
+		));
 	}
 	if let Some(channel) = channels.items.first() {
 		return Ok(channel.id.clone());
 	}
-	Err("No video returned".to_string())
+	Err(KadiumError::new(ErrorKind::NotFound, "No channel returned"))
 }
 
 pub mod channels {
@@ -120,6 +282,7 @@ pub mod videos {
 		pub id: String,
 		pub contentDetails: Option<ContentDetails>,
 		pub liveStreamingDetails: Option<LiveStreamingDetails>,
+		pub player: Option<Player>,
 		pub snippet: Snippet,
 	}
 	#[derive(Deserialize, Debug)]
@@ -130,6 +293,36 @@ pub mod videos {
 	#[allow(non_snake_case)]
 	pub struct LiveStreamingDetails {
 		pub actualStartTime: Option<String>,
+		pub actualEndTime: Option<String>,
+		pub scheduledStartTime: Option<String>,
+		#[allow(dead_code)]
+		pub scheduledEndTime: Option<String>,
+		/// Only while live. The API returns it as a string
+		#[allow(dead_code)]
+		pub concurrentViewers: Option<String>,
+	}
+
+	/// The embed size is only included when `maxHeight` or `maxWidth` is
+	/// requested. It's documented as a number but can arrive as a string
+	#[derive(Deserialize, Debug)]
+	#[allow(non_snake_case)]
+	pub struct Player {
+		pub embedWidth: Option<serde_json::Value>,
+		pub embedHeight: Option<serde_json::Value>,
+	}
+	impl Player {
+		pub fn is_vertical(&self) -> Option<bool> {
+			fn size(value: &Option<serde_json::Value>) -> Option<u64> {
+				match value.as_ref()? {
+					serde_json::Value::Number(n) => n.as_u64(),
+					serde_json::Value::String(s) => s.parse().ok(),
+					_ => None,
+				}
+			}
+			let width = size(&self.embedWidth)?;
+			let height = size(&self.embedHeight)?;
+			Some(height > width)
+		}
 	}
 
 	#[derive(Deserialize, Debug)]
@@ -166,8 +359,11 @@ pub mod playlist_items {
 	/// Lists the fields we use only. Documentation:
 	/// https://developers.google.com/youtube/v3/docs/playlistItems/list#properties
 	#[derive(Deserialize, Debug)]
+	#[allow(non_snake_case)]
 	pub struct Response {
 		pub items: Vec<Playlist>,
+		/// Missing on the last page
+		pub nextPageToken: Option<String>,
 	}
 	/// weird date situation:
 	///  `snippet.publishedAt` is when the video was added to the uploads playlist.
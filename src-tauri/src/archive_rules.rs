@@ -0,0 +1,78 @@
+use crate::error::KadiumError;
+use crate::history::{self, Action};
+use crate::settings::{ArchiveRules, Channel, Settings};
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Archives the channel's videos that match the rules. Returns the IDs of the
+/// archived videos
+async fn apply_to_channel(
+	channel_id: &str,
+	rules: &ArchiveRules,
+	pool: &SqlitePool,
+) -> Result<Vec<String>, KadiumError> {
+	let mut conditions: Vec<&str> = Vec::new();
+	let mut bindings: Vec<i64> = Vec::new();
+	if let Some(days) = rules.older_than_days {
+		conditions.push("publishTimeMs < ?");
+		bindings.push(Utc::now().timestamp_millis() - i64::from(days) * DAY_MS);
+	}
+	if let Some(secs) = rules.shorter_than_secs {
+		// upcoming streams have no duration yet
+		conditions.push("(durationMs > 0 AND durationMs < ?)");
+		bindings.push(i64::from(secs) * 1000);
+	}
+	if rules.live_replays {
+		conditions.push("liveState = 'Replay'");
+	}
+	if conditions.is_empty() {
+		return Ok(Vec::new());
+	}
+	let query_str = format!(
+		"UPDATE videos SET archived = 1 WHERE archived = 0 AND userUnarchived = 0 AND channelId = ? AND ({}) RETURNING id",
+		conditions.join(" OR ")
+	);
+	let mut query = sqlx::query_scalar::<_, String>(&query_str).bind(channel_id);
+	for binding in bindings {
+		query = query.bind(binding);
+	}
+	match query.fetch_all(pool).await {
+		Ok(ids) => Ok(ids),
+		Err(e) => Err(KadiumError::database("Error applying archive rules", e)),
+	}
+}
+
+/// Applies each channel's rules and records the archived videos in the
+/// history. Returns how many videos were archived
+pub async fn apply<'a>(
+	channels: impl IntoIterator<Item = (&'a str, &'a ArchiveRules)>,
+	pool: &SqlitePool,
+) -> Result<usize, KadiumError> {
+	let mut archived_ids = Vec::new();
+	for (channel_id, rules) in channels {
+		if rules.is_empty() {
+			continue;
+		}
+		archived_ids.append(&mut apply_to_channel(channel_id, rules, pool).await?);
+	}
+	let count = archived_ids.len();
+	if count > 0 {
+		history::record(pool, Action::AutoArchive(archived_ids)).await?;
+	}
+	Ok(count)
+}
+
+/// Used on startup, before any channels have been checked
+pub async fn apply_all(
+	settings: &Settings,
+	channels: &[Channel],
+	pool: &SqlitePool,
+) -> Result<usize, KadiumError> {
+	let rules: Vec<_> = channels
+		.iter()
+		.map(|channel| (channel.id.as_str(), settings.archive_rules_for(channel)))
+		.collect();
+	apply(rules.iter().map(|(id, rules)| (*id, rules)), pool).await
+}
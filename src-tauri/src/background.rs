@@ -1,28 +1,44 @@
-use crate::api::{playlist_items, videos, yt_request};
-use crate::{db, settings, throw};
-use chrono::{DateTime, FixedOffset};
+use crate::api::{playlist_items, videos, yt_request, ApiClient, Endpoint};
+use crate::db::LiveState;
+use crate::error::{ErrorKind, KadiumError};
+use crate::notifications::{NotificationQueue, Notifier};
+use crate::reminders::{Reminders, ShownReminders};
+use crate::settings::{ArchiveRules, Backend};
+use crate::title_filter::TitleMatcher;
+use crate::{archive_rules, db, feed, quota, settings};
+use chrono::{DateTime, FixedOffset, Local, Utc};
 use iso8601_duration::Duration as IsoDuration;
 use sqlx::SqlitePool;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 use tauri::{Emitter, Manager};
 use tauri_plugin_notification::NotificationExt;
 use tokio::sync::broadcast;
+use tokio::task::JoinSet;
 use tokio::{task, time};
 
 pub struct IntervalInfo {
 	pub ms: u64,
 	pub channels: Vec<ChannelInfo>,
 }
+#[derive(Clone)]
 pub struct ChannelInfo {
+	pub id: String,
 	pub name: String,
 	pub uploads_playlist_id: String,
 	pub from_time: i64,
+	pub backend: Backend,
+	/// Including the rules of the channel's tags
+	pub archive_rules: ArchiveRules,
+	pub ignore_shorts: bool,
+	pub title_matcher: Arc<TitleMatcher>,
+	pub notify_new_uploads: bool,
 }
 
 pub struct BgHandle {
-	pub handle: thread::JoinHandle<Result<(), String>>,
+	pub handle: thread::JoinHandle<Result<(), KadiumError>>,
 	pub stop_sender: broadcast::Sender<()>,
 }
 
@@ -32,14 +48,17 @@ impl BgHandle {
 		// Error can only occur when channel is already closed
 		let _ = self.stop_sender.send(());
 	}
-	pub fn wait_until_stopped(self) -> Result<(), String> {
+	pub fn wait_until_stopped(self) -> Result<(), KadiumError> {
 		match self.handle.join() {
 			Ok(result) => result,
 			Err(e) => {
 				if let Some(e) = e.downcast_ref::<&'static str>() {
-					throw!("Interval thread error: {}", e);
+					Err(KadiumError::other(format!("Interval thread error: {}", e)))
 				} else {
-					throw!("Unknown interval thread error: {:?}", e);
+					Err(KadiumError::other(format!(
+						"Unknown interval thread error: {:?}",
+						e
+					)))
 				}
 			}
 		}
@@ -48,45 +67,89 @@ impl BgHandle {
 
 pub fn spawn_bg(
 	settings: &settings::Settings,
-	pool: &SqlitePool,
+	channels: &[settings::Channel],
+	api: ApiClient,
+	backoff: &Backoff,
+	notification_queue: &NotificationQueue,
+	shown_reminders: &ShownReminders,
 	window: tauri::WebviewWindow,
 ) -> Option<BgHandle> {
 	if settings.check_in_background {
-		spawn(settings, pool, false, window)
+		spawn(
+			settings,
+			channels,
+			api,
+			backoff,
+			notification_queue,
+			shown_reminders,
+			false,
+			window,
+		)
 	} else {
 		None
 	}
 }
 pub fn spawn_bg_or_check_now(
 	settings: &settings::Settings,
-	pool: &SqlitePool,
+	channels: &[settings::Channel],
+	api: ApiClient,
+	backoff: &Backoff,
+	notification_queue: &NotificationQueue,
+	shown_reminders: &ShownReminders,
 	window: tauri::WebviewWindow,
 ) -> Option<BgHandle> {
 	if settings.check_in_background {
-		spawn(settings, pool, false, window)
+		spawn(
+			settings,
+			channels,
+			api,
+			backoff,
+			notification_queue,
+			shown_reminders,
+			false,
+			window,
+		)
 	} else {
-		spawn(settings, pool, true, window)
+		spawn(
+			settings,
+			channels,
+			api,
+			backoff,
+			notification_queue,
+			shown_reminders,
+			true,
+			window,
+		)
 	}
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn(
 	settings: &settings::Settings,
-	pool: &SqlitePool,
+	channels: &[settings::Channel],
+	api: ApiClient,
+	backoff: &Backoff,
+	notification_queue: &NotificationQueue,
+	shown_reminders: &ShownReminders,
 	run_once: bool,
 	window: tauri::WebviewWindow,
 ) -> Option<BgHandle> {
-	if settings.channels.is_empty() {
+	if channels.is_empty() {
 		return None;
 	}
 
-	let interval_map = new_intervals_map(&settings.channels);
+	let interval_map = new_intervals_map(settings, channels);
 	let interval_infos = interval_map.into_values().collect();
 
 	let (stop_sender, _stop_receiver) = broadcast::channel(1);
 
 	let options = IntervalOptions {
-		pool: pool.clone(),
-		key: settings.api_key_or_default(),
+		pool: api.pool.clone(),
+		api,
+		max_playlist_pages: settings.max_playlist_pages,
+		backoff: backoff.clone(),
+		reminders: Reminders::new(channels, shown_reminders, window.clone()),
+		notifier: Notifier::new(settings, notification_queue, window.clone()),
 		stop_sender: stop_sender.clone(),
 		run_once,
 		window,
@@ -101,7 +164,7 @@ fn spawn(
 }
 
 pub type IntervalMap = HashMap<u64, IntervalInfo>;
-fn new_intervals_map(channels: &[settings::Channel]) -> IntervalMap {
+fn new_intervals_map(settings: &settings::Settings, channels: &[settings::Channel]) -> IntervalMap {
 	let mut intervals_map: IntervalMap = HashMap::new();
 	for channel in channels.iter() {
 		let default = IntervalInfo {
@@ -111,26 +174,129 @@ fn new_intervals_map(channels: &[settings::Channel]) -> IntervalMap {
 		let interval_info = intervals_map
 			.entry(channel.refresh_rate_ms)
 			.or_insert(default);
+		// set_channels rejects invalid filters, but imported settings files
+		// may have been edited by hand
+		let title_matcher = match TitleMatcher::new(&channel.title_filter) {
+			Ok(title_matcher) => title_matcher,
+			Err(e) => {
+				eprintln!("Ignoring title filter of {}: {}", channel.name, e);
+				TitleMatcher::default()
+			}
+		};
 		interval_info.channels.push(ChannelInfo {
+			id: channel.id.clone(),
 			name: channel.name.to_string(),
 			uploads_playlist_id: channel.uploads_playlist_id.clone(),
 			from_time: channel.from_time,
+			backend: channel.backend.unwrap_or(settings.backend),
+			archive_rules: settings.archive_rules_for(channel),
+			ignore_shorts: channel.ignore_shorts,
+			title_matcher: Arc::new(title_matcher),
+			notify_new_uploads: settings.notify_new_uploads_for(channel),
 		});
 	}
 	intervals_map
 }
 
+/// Pauses all intervals after quota or transient API errors
+#[derive(Clone)]
+pub struct Backoff(Arc<Mutex<BackoffState>>);
+struct BackoffState {
+	paused_until: Option<DateTime<Utc>>,
+	/// Consecutive transient failures
+	failures: u32,
+}
+impl Default for Backoff {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl Backoff {
+	pub fn new() -> Self {
+		Self(Arc::new(Mutex::new(BackoffState {
+			paused_until: None,
+			failures: 0,
+		})))
+	}
+	pub fn reset(&self) {
+		let mut state = self.0.lock().unwrap();
+		state.paused_until = None;
+		state.failures = 0;
+	}
+	async fn wait(&self) {
+		let paused_until = self.0.lock().unwrap().paused_until;
+		if let Some(paused_until) = paused_until {
+			if let Ok(duration) = (paused_until - Utc::now()).to_std() {
+				println!("Paused until {}", paused_until);
+				time::sleep(duration).await;
+			}
+		}
+	}
+	fn succeeded(&self) {
+		let mut state = self.0.lock().unwrap();
+		// another interval may have paused while this one was checking
+		let pause_is_over = match state.paused_until {
+			Some(paused_until) => paused_until <= Utc::now(),
+			None => true,
+		};
+		if pause_is_over {
+			state.paused_until = None;
+			state.failures = 0;
+		}
+	}
+	/// Pauses checking. Returns a message if the user should be notified,
+	/// which only happens once per streak of errors.
+	fn failed(&self, error: &KadiumError) -> Option<String> {
+		let mut state = self.0.lock().unwrap();
+		let now = Utc::now();
+		if let Some(paused_until) = state.paused_until {
+			if paused_until > now {
+				return None; // another interval already paused
+			}
+		}
+		if error.is_quota() {
+			let paused_until = quota::next_reset(now);
+			state.paused_until = Some(paused_until);
+			let local_time = paused_until.with_timezone(&Local).format("%H:%M");
+			return Some(format!("Checking will resume at {}", local_time));
+		}
+		state.failures += 1;
+		let exponent = (state.failures - 1).min(6);
+		let delay = (BACKOFF_BASE_MS << exponent).min(BACKOFF_MAX_MS);
+		state.paused_until = Some(now + chrono::Duration::milliseconds(delay));
+		if state.failures == 1 {
+			Some(format!("Retrying in {} minutes", delay / 60_000))
+		} else {
+			None
+		}
+	}
+}
+const BACKOFF_BASE_MS: i64 = 60 * 1000;
+const BACKOFF_MAX_MS: i64 = 60 * 60 * 1000;
+
 #[derive(Clone)]
 struct IntervalOptions {
 	pool: SqlitePool,
-	key: String,
+	api: ApiClient,
+	max_playlist_pages: u32,
+	backoff: Backoff,
+	reminders: Reminders,
+	notifier: Notifier,
 	stop_sender: broadcast::Sender<()>,
 	run_once: bool,
 	window: tauri::WebviewWindow,
 }
 
 #[tokio::main]
-async fn start(options: IntervalOptions, interval_infos: Vec<IntervalInfo>) -> Result<(), String> {
+async fn start(
+	options: IntervalOptions,
+	interval_infos: Vec<IntervalInfo>,
+) -> Result<(), KadiumError> {
+	if !options.run_once {
+		if let Err(e) = options.reminders.sync(&options.pool).await {
+			eprintln!("{}", e);
+		}
+	}
 	let mut tasks = Vec::new();
 	for interval_info in interval_infos {
 		let options = options.clone();
@@ -142,7 +308,7 @@ async fn start(options: IntervalOptions, interval_infos: Vec<IntervalInfo>) -> R
 					match result {
 						Ok(_) => Ok(()),
 						Err(e) => {
-						Err(e.to_string())
+						Err(KadiumError::other(e.to_string()))
 						}
 					}
 				}
@@ -150,11 +316,21 @@ async fn start(options: IntervalOptions, interval_infos: Vec<IntervalInfo>) -> R
 		});
 		tasks.push(handle);
 	}
+	if !options.run_once {
+		let notifier = options.notifier.clone();
+		let mut stop_receiver = options.stop_sender.subscribe();
+		tasks.push(task::spawn(async move {
+			tokio::select! {
+				_ = notifier.run() => Ok(()),
+				_ = stop_receiver.recv() => Ok(()),
+			}
+		}));
+	}
 	for task in tasks {
 		match task.await {
 			Ok(result) => result?,
 			Err(e) => {
-				return Err(e.to_string());
+				return Err(KadiumError::other(e.to_string()));
 			}
 		}
 	}
@@ -180,6 +356,7 @@ async fn run_interval(options: IntervalOptions, interval_info: IntervalInfo) {
 	interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
 	loop {
 		interval.tick().await;
+		options.backoff.wait().await;
 		println!("Start checking {}ms task", interval_info.ms);
 		check_channels(&options, &interval_info).await;
 		println!("Done checking {}ms task", interval_info.ms);
@@ -204,106 +381,349 @@ async fn check_channels(options: &IntervalOptions, interval_info: &IntervalInfo)
 	if window_visible {
 		let _ = options.window.emit("checking", "");
 	}
-	for channel in &interval_info.channels {
-		match check_channel(options, channel).await {
-			Ok(()) => {}
+	// Check up to `limit` channels at a time. The limiter itself is what
+	// keeps requests across all intervals within the limit.
+	let mut tasks = JoinSet::new();
+	let mut channels = interval_info.channels.iter();
+	let mut failed = false;
+	let mut backed_off = false;
+	let mut new_videos: Vec<db::Video> = Vec::new();
+	loop {
+		// after a failure, checks in flight still finish so their new videos
+		// get notified, but no more are started
+		while !failed && tasks.len() < options.api.limiter.limit() {
+			let channel = match channels.next() {
+				Some(channel) => channel.clone(),
+				None => break,
+			};
+			let options = options.clone();
+			tasks.spawn(async move {
+				let result = check_channel(&options, &channel).await;
+				(channel, result)
+			});
+		}
+		let (channel, result) = match tasks.join_next().await {
+			Some(Ok(task_result)) => task_result,
+			Some(Err(e)) => {
+				eprintln!("Channel check task failed: {}", e);
+				if !failed {
+					app.notification()
+						.builder()
+						.title("Failed to check channels")
+						.body(e.to_string())
+						.show()
+						.expect("Unable to show notification");
+				}
+				failed = true;
+				continue;
+			}
+			None => break,
+		};
+		match result {
+			Ok(mut videos) => {
+				if channel.notify_new_uploads {
+					new_videos.append(&mut videos);
+				}
+			}
+			Err(e) if (e.is_transient() || e.is_quota()) && !backed_off => {
+				eprintln!("Error checking {}: {}", channel.name, e);
+				if let Some(resume_message) = options.backoff.failed(&e) {
+					app.notification()
+						.builder()
+						.title(format!("Paused checking: {}", e))
+						.body(resume_message)
+						.show()
+						.expect("Unable to show notification");
+				}
+				backed_off = true;
+				failed = true;
+			}
+			Err(e) if failed => {
+				eprintln!("Error checking {}: {}", channel.name, e);
+			}
 			Err(e) => {
 				let title = format!("Error checking {}", channel.name);
 				eprintln!("{}: {}", title, e);
 				app.notification()
 					.builder()
 					.title(title)
-					.body(e)
+					.body(e.to_string())
 					.show()
 					.expect("Unable to show notification");
-				break;
+				failed = true;
 			}
 		}
 	}
+	if !failed {
+		options.backoff.succeeded();
+	}
+	if options.run_once {
+		// checking manually isn't held back by the schedule
+		options.notifier.show(&new_videos);
+	} else {
+		options.notifier.notify(new_videos);
+	}
+	let channel_rules = interval_info
+		.channels
+		.iter()
+		.map(|channel| (channel.id.as_str(), &channel.archive_rules));
+	match archive_rules::apply(channel_rules, &options.pool).await {
+		Ok(0) => {}
+		Ok(_) => {
+			let _ = options.window.emit("refresh", "");
+		}
+		Err(e) => eprintln!("{}", e),
+	}
+	// reminders only run while the background runtime does
+	if !options.run_once {
+		if let Err(e) = options.reminders.sync(&options.pool).await {
+			eprintln!("{}", e);
+		}
+	}
 	if window_visible {
 		let _ = options.window.emit("doneChecking", "");
 	}
 }
 
-/// Returns the number of new videos saved
-async fn check_channel(options: &IntervalOptions, channel: &ChannelInfo) -> Result<(), String> {
+/// Returns the new videos that were saved unarchived
+async fn check_channel(
+	options: &IntervalOptions,
+	channel: &ChannelInfo,
+) -> Result<Vec<db::Video>, KadiumError> {
 	println!("Checking {} {}", channel.uploads_playlist_id, channel.name);
-	let url = "https://www.googleapis.com/youtube/v3/playlistItems".to_string()
-		+ "?part=contentDetails"
-		+ "&maxResults=50"
-		+ "&playlistId="
-		+ &channel.uploads_playlist_id;
-	let uploads = yt_request::<playlist_items::Response>(&url, &options.key)
-		.await
-		.map_err(|e| format!("Failed to get channel: {}", e))?;
 
-	if uploads.items.is_empty() {
-		return Ok(()); // no channel videos returned
+	let new_ids = match channel.backend {
+		Backend::DataApi => get_new_ids_from_playlist(options, channel).await?,
+		Backend::Feed => get_new_ids_from_feed(options, channel).await?,
+	};
+
+	// upcoming and live videos are checked again until they end
+	let live_ids = db::get_live_ids(&channel.id, &options.pool).await?;
+
+	if new_ids.is_empty() && live_ids.is_empty() {
+		return Ok(Vec::new()); // no new videos
 	}
 
-	let existing_ids = db::get_ids(&uploads.items, &options.pool).await?;
+	// get info about the videos
+	println!(
+		"Checking videos from {} {}",
+		channel.uploads_playlist_id, channel.name
+	);
+	let ids: Vec<String> = new_ids.iter().chain(live_ids.iter()).cloned().collect();
+	let mut fetched_videos: Vec<db::Video> = Vec::new();
+	let mut batch_errors: Vec<KadiumError> = Vec::new();
+	for ids in ids.chunks(MAX_IDS_PER_REQUEST) {
+		match get_videos(options, ids).await {
+			Ok(mut videos) => fetched_videos.append(&mut videos),
+			Err(e) => batch_errors.push(e),
+		}
+	}
+
+	let mut new_videos: Vec<db::Video> = Vec::new();
+	for video in &fetched_videos {
+		if live_ids.contains(&video.id) {
+			db::update_live_video(video, &options.pool).await?;
+		} else if video.isShort && channel.ignore_shorts {
+			db::ignore_video(&video.id, &options.pool).await?;
+		} else if !channel.title_matcher.allows(&video.title) {
+			if channel.title_matcher.archive_filtered {
+				let mut video = video.clone();
+				video.archived = true;
+				db::insert_video(&video, &options.pool).await?;
+			} else {
+				db::ignore_video(&video.id, &options.pool).await?;
+			}
+		} else {
+			db::insert_video(video, &options.pool).await?;
+			new_videos.push(video.clone());
+		}
+	}
+	if batch_errors.is_empty() {
+		for id in &live_ids {
+			if !fetched_videos.iter().any(|video| &video.id == id) {
+				// the stream was cancelled
+				db::delete_video(id, &options.pool).await?;
+			}
+		}
+	}
+	if !fetched_videos.is_empty() || !live_ids.is_empty() {
+		match options.window.emit("refresh", "") {
+			Ok(_) => {}
+			Err(e) => {
+				return Err(KadiumError::other(format!("Failed to emit refresh: {}", e)));
+			}
+		};
+	}
+	if !batch_errors.is_empty() {
+		let batch_count = ids.chunks(MAX_IDS_PER_REQUEST).len();
+		let error_count = batch_errors.len();
+		let mut messages: Vec<String> = Vec::new();
+		for e in batch_errors {
+			match e {
+				// let the scheduler back off
+				e if e.is_transient() || e.is_quota() => {
+					return Err(e);
+				}
+				e => messages.push(e.to_string()),
+			}
+		}
+		return Err(KadiumError::other(format!(
+			"Failed to get {} of {} batches of videos:\n{}",
+			error_count,
+			batch_count,
+			messages.join("\n")
+		)));
+	}
+	Ok(new_videos)
+}
 
-	// check which videos are new
+async fn get_new_ids_from_playlist(
+	options: &IntervalOptions,
+	channel: &ChannelInfo,
+) -> Result<Vec<String>, KadiumError> {
 	let mut new_ids: Vec<String> = Vec::new();
-	for fetched_video in uploads.items {
-		let fetched_id = &fetched_video.contentDetails.videoId;
-		if existing_ids.contains(fetched_id) {
-			continue;
+	let mut page_token: Option<String> = None;
+	let mut page_count = 0;
+	loop {
+		let mut query = "?part=contentDetails".to_string()
+			+ "&maxResults=50"
+			+ "&playlistId="
+			+ &channel.uploads_playlist_id;
+		if let Some(page_token) = &page_token {
+			query = query + "&pageToken=" + page_token;
+		}
+		let uploads =
+			yt_request::<playlist_items::Response>(Endpoint::PlaylistItems, &query, &options.api)
+				.await?;
+		page_count += 1;
+
+		if uploads.items.is_empty() {
+			break; // no channel videos returned
 		}
 
-		let published_str = match fetched_video.contentDetails.videoPublishedAt {
-			Some(published_str) => published_str,
-			None => {
-				// video isn't public
+		let fetched_ids: Vec<String> = uploads
+			.items
+			.iter()
+			.map(|item| item.contentDetails.videoId.clone())
+			.collect();
+		let existing_ids = db::get_ids(&fetched_ids, &options.pool).await?;
+
+		// check which videos are new. The uploads playlist is roughly sorted
+		// newest first, so we finish the page before stopping.
+		let mut reached_known_videos = false;
+		for fetched_video in uploads.items {
+			let fetched_id = &fetched_video.contentDetails.videoId;
+			if existing_ids.contains(fetched_id) {
+				reached_known_videos = true;
 				continue;
 			}
-		};
-		let published_time = parse_datetime(&published_str)?.timestamp_millis();
-		if published_time < channel.from_time {
-			continue;
+
+			let published_str = match fetched_video.contentDetails.videoPublishedAt {
+				Some(published_str) => published_str,
+				None => {
+					// video isn't public
+					continue;
+				}
+			};
+			let published_time = parse_datetime(&published_str)?.timestamp_millis();
+			if published_time < channel.from_time {
+				reached_known_videos = true;
+				continue;
+			}
+
+			new_ids.push(fetched_video.contentDetails.videoId);
 		}
 
-		new_ids.push(fetched_video.contentDetails.videoId);
+		if reached_known_videos {
+			break;
+		}
+		page_token = match uploads.nextPageToken {
+			Some(next_page_token) => Some(next_page_token),
+			None => break,
+		};
+		if page_count >= options.max_playlist_pages {
+			println!(
+				"Stopped checking {} after {} pages",
+				channel.name, page_count
+			);
+			break;
+		}
 	}
 
-	if new_ids.is_empty() {
-		return Ok(()); // no new videos
+	Ok(new_ids)
+}
+
+/// Videos from the feed still need a `videos.list` request for duration and
+/// livestream details, but that only happens when there are new videos
+async fn get_new_ids_from_feed(
+	options: &IntervalOptions,
+	channel: &ChannelInfo,
+) -> Result<Vec<String>, KadiumError> {
+	let feed_videos = feed::get(&channel.id, &options.api).await?;
+	let fetched_ids: Vec<String> = feed_videos.iter().map(|video| video.id.clone()).collect();
+	let existing_ids = db::get_ids(&fetched_ids, &options.pool).await?;
+
+	let mut new_ids: Vec<String> = Vec::new();
+	for video in feed_videos {
+		if existing_ids.contains(&video.id) || video.publishTimeMs < channel.from_time {
+			continue;
+		}
+		new_ids.push(video.id);
 	}
+	Ok(new_ids)
+}
 
-	// get info about the videos
-	println!(
-		"Checking videos from {} {}",
-		channel.uploads_playlist_id, channel.name
-	);
-	let url = "https://www.googleapis.com/youtube/v3/videos".to_string()
-		+ "?part=contentDetails,liveStreamingDetails,snippet"
+/// The YouTube API rejects requests for more than 50 IDs
+const MAX_IDS_PER_REQUEST: usize = 50;
+
+async fn get_videos(
+	options: &IntervalOptions,
+	ids: &[String],
+) -> Result<Vec<db::Video>, KadiumError> {
+	// maxHeight makes the response include the embed size
+	let query = "?part=contentDetails,liveStreamingDetails,player,snippet".to_string()
+		+ "&maxHeight=8192"
 		+ "&id="
-		+ &new_ids.join(",");
-	let videos = yt_request::<videos::Response>(&url, &options.key)
-		.await
-		.map_err(|e| format!("Failed to get videos: {}", e))?;
+		+ &ids.join(",");
+	let videos = yt_request::<videos::Response>(Endpoint::Videos, &query, &options.api).await?;
 
 	let mut videos_to_add: Vec<db::Video> = Vec::new();
 	for video in videos.items {
-		let content_details = video.contentDetails.ok_or("No contentDetails")?;
-		let duration = match content_details.duration {
-			Some(duration) => duration,
-			None => {
-				// Scheduled live streams don't have a duration
-				continue;
-			}
+		let content_details = video
+			.contentDetails
+			.ok_or_else(|| KadiumError::new(ErrorKind::Parse, "No contentDetails"))?;
+		let duration_ms = match content_details.duration {
+			Some(duration) => parse_absolute_duration(&duration)?,
+			// Scheduled live streams don't have a duration
+			None => 0,
 		};
-		let publish_time = match video.liveStreamingDetails {
-			Some(live_streaming_details) => match live_streaming_details.actualStartTime {
-				Some(actual_start_time) => parse_datetime(&actual_start_time)?,
-				None => {
-					// skip future livestreams
-					continue;
+		let mut scheduled_start_ms = None;
+		let (live_state, publish_time) = match video.liveStreamingDetails {
+			Some(details) => {
+				if let Some(scheduled_start_time) = &details.scheduledStartTime {
+					let scheduled_start = parse_datetime(scheduled_start_time)?;
+					scheduled_start_ms = Some(scheduled_start.timestamp_millis());
 				}
-			},
-			None => parse_datetime(&video.snippet.publishedAt)?,
+				match (details.actualStartTime, details.actualEndTime) {
+					(Some(start), Some(_)) => (LiveState::Replay, parse_datetime(&start)?),
+					(Some(start), None) => (LiveState::Live, parse_datetime(&start)?),
+					(None, _) => {
+						// sorted by when it's scheduled to start
+						let time = details
+							.scheduledStartTime
+							.as_deref()
+							.unwrap_or(&video.snippet.publishedAt);
+						(LiveState::Upcoming, parse_datetime(time)?)
+					}
+				}
+			}
+			None => (LiveState::None, parse_datetime(&video.snippet.publishedAt)?),
 		};
-		let duration_ms = parse_absolute_duration(&duration)?;
+		let vertical = video
+			.player
+			.as_ref()
+			.and_then(|player| player.is_vertical());
+		let is_short = is_short(duration_ms, live_state, vertical);
 		videos_to_add.push(db::Video {
 			id: video.id,
 			title: video.snippet.title,
@@ -316,33 +736,43 @@ async fn check_channel(options: &IntervalOptions, channel: &ChannelInfo) -> Resu
 			channelName: video.snippet.channelTitle,
 			unread: true,
 			archived: false,
+			liveState: live_state,
+			scheduledStartMs: scheduled_start_ms,
+			isShort: is_short,
 		});
 	}
+	Ok(videos_to_add)
+}
 
-	for video in &videos_to_add {
-		db::insert_video(video, &options.pool).await?;
+/// Shorts can be up to 3 minutes long
+const MAX_SHORT_DURATION_MS: i64 = 3 * 60 * 1000;
+
+/// Shorts are vertical and short. The API has no field for it, and checking
+/// the channel's Shorts playlist would cost quota on every check. Without an
+/// embed size, only videos up to the original 60 second limit count.
+fn is_short(duration_ms: i64, live_state: LiveState, vertical: Option<bool>) -> bool {
+	if live_state != LiveState::None || duration_ms == 0 || duration_ms > MAX_SHORT_DURATION_MS {
+		return false;
 	}
-	if !videos_to_add.is_empty() {
-		match options.window.emit("refresh", "") {
-			Ok(_) => {}
-			Err(e) => {
-				return Err(format!("Failed to emit refresh: {}", e));
-			}
-		};
+	match vertical {
+		Some(vertical) => vertical,
+		None => duration_ms <= 60 * 1000,
 	}
-	Ok(())
 }
 
-pub fn parse_datetime(value: &str) -> Result<DateTime<FixedOffset>, String> {
+pub fn parse_datetime(value: &str) -> Result<DateTime<FixedOffset>, KadiumError> {
 	match DateTime::parse_from_rfc3339(value) {
 		Ok(datetime) => Ok(datetime),
-		Err(e) => throw!("Unexpected video publish date: {}", e),
+		Err(e) => Err(KadiumError::new(
+			ErrorKind::Parse,
+			format!("Unexpected video publish date: {}", e),
+		)),
 	}
 }
 /// Parse a duration that cannot include year or month, because
 /// years and months have different lengths depending on what month or year
 /// it is.
-pub fn parse_absolute_duration(value: &str) -> Result<i64, String> {
+pub fn parse_absolute_duration(value: &str) -> Result<i64, KadiumError> {
 	match IsoDuration::parse(value) {
 		Ok(duration) => {
 			if duration.month == 0.0 && duration.year == 0.0 {
@@ -355,9 +785,15 @@ pub fn parse_absolute_duration(value: &str) -> Result<i64, String> {
 				let ms_int = ms_clamped.round() as i64;
 				Ok(ms_int)
 			} else {
-				throw!("Cannot parse duration with year or month: {}", value);
+				Err(KadiumError::new(
+					ErrorKind::Parse,
+					format!("Cannot parse duration with year or month: {}", value),
+				))
 			}
 		}
-		Err(e) => throw!("Unexpected video duration: {e:?}"),
+		Err(e) => Err(KadiumError::new(
+			ErrorKind::Parse,
+			format!("Unexpected video duration: {e:?}"),
+		)),
 	}
 }
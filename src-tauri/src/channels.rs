@@ -0,0 +1,159 @@
+use crate::error::KadiumError;
+use crate::settings::{ArchiveRules, Backend, Channel, TitleFilter};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+
+/// Channel options without a column of their own, saved as JSON so new ones
+/// don't need a migration
+#[derive(Serialize, Deserialize)]
+struct Options {
+	#[serde(default)]
+	backend: Option<Backend>,
+	#[serde(default)]
+	archive_rules: ArchiveRules,
+	#[serde(default)]
+	remind_minutes_before: Option<u32>,
+	#[serde(default)]
+	ignore_shorts: bool,
+	#[serde(default)]
+	title_filter: TitleFilter,
+	#[serde(default)]
+	notify_new_uploads: Option<bool>,
+}
+impl From<&Channel> for Options {
+	fn from(channel: &Channel) -> Self {
+		Options {
+			backend: channel.backend,
+			archive_rules: channel.archive_rules.clone(),
+			remind_minutes_before: channel.remind_minutes_before,
+			ignore_shorts: channel.ignore_shorts,
+			title_filter: channel.title_filter.clone(),
+			notify_new_uploads: channel.notify_new_uploads,
+		}
+	}
+}
+
+fn channel_from_row(row: &SqliteRow, tags: Vec<String>) -> sqlx::Result<Channel> {
+	let options: String = row.try_get("options")?;
+	let options: Options =
+		serde_json::from_str(&options).map_err(|e| sqlx::Error::ColumnDecode {
+			index: "options".to_string(),
+			source: Box::new(e),
+		})?;
+	let refresh_rate_ms: i64 = row.try_get("refreshRateMs")?;
+	Ok(Channel {
+		id: row.try_get("id")?,
+		name: row.try_get("name")?,
+		icon: row.try_get("icon")?,
+		uploads_playlist_id: row.try_get("uploadsPlaylistId")?,
+		from_time: row.try_get("fromTime")?,
+		// SQLite does not support unsigned integers
+		refresh_rate_ms: refresh_rate_ms as u64,
+		tags,
+		backend: options.backend,
+		archive_rules: options.archive_rules,
+		remind_minutes_before: options.remind_minutes_before,
+		ignore_shorts: options.ignore_shorts,
+		title_filter: options.title_filter,
+		notify_new_uploads: options.notify_new_uploads,
+	})
+}
+
+/// The channel list, in order. Removed channels are left out
+pub async fn get_all(pool: &SqlitePool) -> Result<Vec<Channel>, KadiumError> {
+	let tag_rows: Vec<(String, String)> =
+		match sqlx::query_as("SELECT channelId, tag FROM channelTags ORDER BY channelId, position")
+			.fetch_all(pool)
+			.await
+		{
+			Ok(rows) => rows,
+			Err(e) => return Err(KadiumError::database("Error getting channel tags", e)),
+		};
+	let rows =
+		match sqlx::query("SELECT * FROM channels WHERE position IS NOT NULL ORDER BY position")
+			.fetch_all(pool)
+			.await
+		{
+			Ok(rows) => rows,
+			Err(e) => return Err(KadiumError::database("Error getting channels", e)),
+		};
+	let mut channels = Vec::new();
+	for row in rows {
+		let id: String = match row.try_get("id") {
+			Ok(id) => id,
+			Err(e) => return Err(KadiumError::database("Error getting channels", e)),
+		};
+		let tags = tag_rows
+			.iter()
+			.filter(|(channel_id, _)| *channel_id == id)
+			.map(|(_, tag)| tag.clone())
+			.collect();
+		match channel_from_row(&row, tags) {
+			Ok(channel) => channels.push(channel),
+			Err(e) => return Err(KadiumError::database("Error getting channels", e)),
+		}
+	}
+	Ok(channels)
+}
+
+/// Replaces the channel list. Channels that are no longer listed keep their
+/// row, because their videos refer to it
+pub async fn save_all(channels: &[Channel], pool: &SqlitePool) -> Result<(), KadiumError> {
+	let mut tx = match pool.begin().await {
+		Ok(tx) => tx,
+		Err(e) => return Err(KadiumError::database("Error saving channels", e)),
+	};
+	if let Err(e) = sqlx::query("UPDATE channels SET position = NULL")
+		.execute(&mut *tx)
+		.await
+	{
+		return Err(KadiumError::database("Error saving channels", e));
+	}
+	if let Err(e) = sqlx::query("DELETE FROM channelTags")
+		.execute(&mut *tx)
+		.await
+	{
+		return Err(KadiumError::database("Error saving channel tags", e));
+	}
+	for (position, channel) in channels.iter().enumerate() {
+		let options = match serde_json::to_string(&Options::from(channel)) {
+			Ok(options) => options,
+			Err(e) => return Err(KadiumError::database("Error saving channels", e)),
+		};
+		let query = sqlx::query(
+			"INSERT INTO channels (id,name,icon,uploadsPlaylistId,fromTime,refreshRateMs,position,options) \
+			VALUES (?,?,?,?,?,?,?,?) \
+			ON CONFLICT (id) DO UPDATE SET name = excluded.name, icon = excluded.icon, \
+			uploadsPlaylistId = excluded.uploadsPlaylistId, fromTime = excluded.fromTime, \
+			refreshRateMs = excluded.refreshRateMs, position = excluded.position, \
+			options = excluded.options",
+		)
+		.bind(&channel.id)
+		.bind(&channel.name)
+		.bind(&channel.icon)
+		.bind(&channel.uploads_playlist_id)
+		.bind(channel.from_time)
+		.bind(channel.refresh_rate_ms as i64)
+		.bind(position as i64)
+		.bind(options);
+		if let Err(e) = query.execute(&mut *tx).await {
+			return Err(KadiumError::database("Error saving channels", e));
+		}
+		for (tag_position, tag) in channel.tags.iter().enumerate() {
+			let query = sqlx::query(
+				"INSERT OR IGNORE INTO channelTags (channelId,tag,position) VALUES (?,?,?)",
+			)
+			.bind(&channel.id)
+			.bind(tag)
+			.bind(tag_position as i64);
+			if let Err(e) = query.execute(&mut *tx).await {
+				return Err(KadiumError::database("Error saving channel tags", e));
+			}
+		}
+	}
+	if let Err(e) = tx.commit().await {
+		return Err(KadiumError::database("Error saving channels", e));
+	}
+	Ok(())
+}
@@ -1,18 +1,21 @@
-use crate::api::{channels, yt_request};
-use crate::settings::{Channel, Settings, VersionedSettings};
-use crate::{api, background, throw};
+use crate::api::{yt_request, ApiClient, Endpoint, RequestLimiter};
+use crate::error::{ErrorKind, KadiumError};
+use crate::history::{Action, UndoHistory};
+use crate::notifications::NotificationQueue;
+use crate::reminders::ShownReminders;
+use crate::settings::{ArchiveRules, Channel, Settings, TitleFilter, VersionedSettings};
+use crate::title_filter::TitleMatcher;
+use crate::{api, background, channels};
 use atomicwrites::{AtomicFile, OverwriteBehavior};
 use scraper::{Html, Selector};
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use specta::Type;
 use sqlx::SqlitePool;
 use std::collections::HashSet;
-use std::convert::TryInto;
 use std::env;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::{command, Config, Error, Manager, State};
 use tokio::sync::Mutex;
 use url::Url;
@@ -44,7 +47,15 @@ impl AppPaths {
 pub struct Data {
 	pub bg_handle: Option<background::BgHandle>,
 	pub db_pool: SqlitePool,
+	pub limiter: RequestLimiter,
+	pub http_client: reqwest::Client,
+	// these outlive background restarts, so changing settings doesn't reset them
+	pub backoff: background::Backoff,
+	pub notification_queue: NotificationQueue,
+	pub shown_reminders: ShownReminders,
 	pub versioned_settings: VersionedSettings,
+	/// Kept in sync with the database by `save_channels`
+	pub channels: Vec<Channel>,
 	pub paths: AppPaths,
 	pub window: tauri::WebviewWindow,
 	pub user_history: UndoHistory,
@@ -56,65 +67,100 @@ impl Data {
 	pub fn settings_ref(&self) -> &Settings {
 		self.versioned_settings.unwrap_ref()
 	}
-	pub fn restart_background(&mut self) -> Result<(), String> {
+	pub fn api_client(&self) -> ApiClient {
+		ApiClient {
+			key: self.settings_ref().api_key_or_default(),
+			base_url: self.settings_ref().api_base_url_or_default(),
+			feed_base_url: self.settings_ref().feed_base_url_or_default(),
+			limiter: self.limiter.clone(),
+			pool: self.db_pool.clone(),
+			http: self.http_client.clone(),
+		}
+	}
+	pub fn restart_background(&mut self) -> Result<(), KadiumError> {
 		if let Some(bg_handle) = self.bg_handle.take() {
 			bg_handle.stop();
 			bg_handle.wait_until_stopped()?;
 		}
-		self.bg_handle =
-			background::spawn_bg(self.settings_ref(), &self.db_pool, self.window.clone());
+		self.bg_handle = background::spawn_bg(
+			self.settings_ref(),
+			&self.channels,
+			self.api_client(),
+			&self.backoff,
+			&self.notification_queue,
+			&self.shown_reminders,
+			self.window.clone(),
+		);
 		Ok(())
 	}
-	pub fn check_now(&mut self) -> Result<(), String> {
+	pub fn check_now(&mut self) -> Result<(), KadiumError> {
 		if let Some(bg_handle) = self.bg_handle.take() {
 			bg_handle.stop();
 			bg_handle.wait_until_stopped()?;
 		}
 		self.bg_handle = background::spawn_bg_or_check_now(
 			self.settings_ref(),
-			&self.db_pool,
+			&self.channels,
+			self.api_client(),
+			&self.backoff,
+			&self.notification_queue,
+			&self.shown_reminders,
 			self.window.clone(),
 		);
 		Ok(())
 	}
-	pub fn save_settings(&mut self) -> Result<(), String> {
+	pub fn save_settings(&mut self) -> Result<(), KadiumError> {
+		self.http_client = api::http_client_or_without_proxy(self.settings_ref());
 		self.versioned_settings.save(&self.paths)?;
 		self.restart_background()?;
 		Ok(())
 	}
+	pub async fn save_channels(&mut self, channels: Vec<Channel>) -> Result<(), KadiumError> {
+		channels::save_all(&channels, &self.db_pool).await?;
+		self.channels = channels;
+		self.restart_background()?;
+		Ok(())
+	}
 }
 
-pub fn ensure_parent_exists(file_path: &Path) -> Result<(), String> {
+pub fn ensure_parent_exists(file_path: &Path) -> Result<(), KadiumError> {
 	if let Some(parent) = file_path.parent() {
 		if let Err(e) = std::fs::create_dir_all(parent) {
-			throw!("Error creating parent folder: {}", e.to_string());
+			return Err(KadiumError::file("Error creating parent folder", e));
 		}
 	}
 	Ok(())
 }
 
-pub fn write_atomically(file_path: &PathBuf, buf: &[u8]) -> Result<(), String> {
+pub fn write_atomically(file_path: &PathBuf, buf: &[u8]) -> Result<(), KadiumError> {
 	ensure_parent_exists(file_path)?;
 	let af = AtomicFile::new(file_path, OverwriteBehavior::AllowOverwrite);
 	match af.write(|f| f.write_all(buf)) {
 		Ok(_) => Ok(()),
-		Err(e) => Err(e.to_string()),
+		Err(e) => Err(KadiumError::new(ErrorKind::File, e.to_string())),
 	}
 }
 
 #[command]
 #[specta::specta]
-pub async fn get_settings(data: DataState<'_>) -> Result<Settings, String> {
+pub async fn get_settings(data: DataState<'_>) -> Result<Settings, KadiumError> {
 	let mut data = data.0.lock().await;
 	Ok(data.settings().clone())
 }
 
 #[command]
 #[specta::specta]
-pub async fn tags(data: DataState<'_>) -> Result<Vec<String>, String> {
+pub async fn get_channels(data: DataState<'_>) -> Result<Vec<Channel>, KadiumError> {
+	let data = data.0.lock().await;
+	Ok(data.channels.clone())
+}
+
+#[command]
+#[specta::specta]
+pub async fn tags(data: DataState<'_>) -> Result<Vec<String>, KadiumError> {
 	let data = data.0.lock().await;
 	let mut tags_set: HashSet<String> = HashSet::new();
-	for channel in &data.settings_ref().channels {
+	for channel in &data.channels {
 		for tag in &channel.tags {
 			tags_set.insert(tag.clone());
 		}
@@ -126,10 +172,10 @@ pub async fn tags(data: DataState<'_>) -> Result<Vec<String>, String> {
 
 #[command]
 #[specta::specta]
-pub async fn check_now(data: DataState<'_>) -> Result<(), String> {
+pub async fn check_now(data: DataState<'_>) -> Result<(), KadiumError> {
 	let mut data = data.0.lock().await;
 	data.check_now()?;
-	data.user_history.push(Action::CheckNow);
+	data.user_history.push(Action::CheckNow).await;
 	Ok(())
 }
 
@@ -185,98 +231,124 @@ fn url_parse_username(value: &str) -> Option<String> {
 	Some(path_segments.next()?.to_string())
 }
 
-async fn get_channel_id_from_url(url: &str) -> Result<String, String> {
-	let client = reqwest::Client::new();
-	let text = client
+async fn get_channel_id_from_url(url: &str, api_client: &ApiClient) -> Result<String, KadiumError> {
+	let _permit = api_client.limiter.acquire().await?;
+	let text = api_client
+		.http
 		.get(url)
 		.send()
 		.await
-		.map_err(|e| format!("API request failed: {}", e))?
+		.map_err(|e| KadiumError::new(ErrorKind::Network, format!("Request failed: {}", e)))?
 		.text()
 		.await
-		.map_err(|e| format!("API response was not JSON: {}", e))?;
+		.map_err(|e| {
+			KadiumError::new(ErrorKind::Network, format!("Response was not text: {}", e))
+		})?;
 
+	let invalid_url = |message: &str| KadiumError::new(ErrorKind::InvalidUrl, message);
 	let html = Html::parse_document(&text);
 	let selector = Selector::parse("link[rel='canonical'][href*='youtube.com']").unwrap();
 	let link_elements = html.select(&selector).collect::<Vec<_>>();
 	if link_elements.len() > 1 {
-		return Err("Multiple canonical URL elements in the page".to_string());
+		return Err(invalid_url("Multiple canonical URL elements in the page"));
 	}
 	let link_element = link_elements
 		.first()
-		.ok_or("No canonical URL element in the page")?;
+		.ok_or_else(|| invalid_url("No canonical URL element in the page"))?;
 	let canonical_url = link_element
 		.attr("href")
-		.ok_or("No canonical URL href in the page")?;
+		.ok_or_else(|| invalid_url("No canonical URL href in the page"))?;
 
-	url_parse_channel_id(canonical_url).ok_or(format!("Unexpected canonical URL: {canonical_url}"))
+	url_parse_channel_id(canonical_url)
+		.ok_or_else(|| invalid_url(&format!("Unexpected canonical URL: {canonical_url}")))
 }
 
-async fn get_id_from_url(url: &str, key: &str) -> Result<String, String> {
+async fn get_id_from_url(url: &str, api_client: &ApiClient) -> Result<String, KadiumError> {
 	if let Some(video_id) = url_parse_video_id(&url) {
-		api::channel_id_from_video_id(&video_id, key).await
+		api::channel_id_from_video_id(&video_id, api_client).await
 	} else if let Some(id) = url_parse_channel_id(&url) {
 		Ok(id)
 	} else if let Some(username) = url_parse_username(&url) {
-		api::channel_id_from_username(&username, key).await
+		api::channel_id_from_username(&username, api_client).await
 	} else {
-		get_channel_id_from_url(&url).await.map_err(|e| {
-			format!(
-				"Invalid URL. You could try a video URL from the channel.\n\n{}",
-				e
-			)
-		})
+		get_channel_id_from_url(&url, api_client)
+			.await
+			.map_err(|e| match e.kind {
+				ErrorKind::InvalidUrl => {
+					e.context("Invalid URL. You could try a video URL from the channel")
+				}
+				_ => e,
+			})
 	}
 }
 
 #[command]
 #[specta::specta]
-pub async fn set_channels(channels: Vec<Channel>, data: DataState<'_>) -> Result<(), String> {
+pub async fn set_channels(channels: Vec<Channel>, data: DataState<'_>) -> Result<(), KadiumError> {
+	for channel in &channels {
+		TitleMatcher::new(&channel.title_filter)
+			.map_err(|e| e.context(format!("Invalid title filter for {}", channel.name)))?;
+	}
 	let mut data = data.0.lock().await;
-	data.settings().channels = channels;
-	data.save_settings()?;
+	let previous = data.channels.clone();
+	data.save_channels(channels.clone()).await?;
 	data.user_history
-		.push(Action::UpdateOrDeleteChannels("".to_string()));
+		.push(Action::UpdateOrDeleteChannels {
+			previous,
+			new: channels,
+		})
+		.await;
 	Ok(())
 }
 
 #[command]
 #[specta::specta]
-pub async fn add_channel(options: AddChannelOptions, data: DataState<'_>) -> Result<(), String> {
+pub async fn add_channel(
+	options: AddChannelOptions,
+	data: DataState<'_>,
+) -> Result<(), KadiumError> {
 	let mut data = data.0.lock().await;
-	let settings = data.settings();
+	let api_client = data.api_client();
 
-	let id = get_id_from_url(&options.url, &settings.api_key_or_default()).await?;
+	let id = get_id_from_url(&options.url, &api_client).await?;
 
-	for channel in &settings.channels {
+	for channel in &data.channels {
 		if channel.id == id {
-			throw!("Channel already exists");
+			return Err(KadiumError::new(
+				ErrorKind::ChannelExists,
+				"Channel already exists",
+			));
 		}
 	}
 
-	let url = "https://www.googleapis.com/youtube/v3/channels".to_owned()
-		+ "?part=contentDetails,id,snippet"
-		+ "&id="
-		+ &id;
-	let channels = yt_request::<channels::Response>(&url, &settings.api_key_or_default())
+	let query = "?part=contentDetails,id,snippet".to_owned() + "&id=" + &id;
+	let channels = yt_request::<api::channels::Response>(Endpoint::Channels, &query, &api_client)
 		.await
-		.map_err(|e| format!("Failed to get channel: {}", e))?;
+		.map_err(|e| e.context("Failed to get channel"))?;
 	let channel = match channels.items.into_iter().next() {
 		Some(channel) => channel,
-		None => throw!("No channel found"),
+		None => return Err(KadiumError::new(ErrorKind::NotFound, "No channel found")),
 	};
 
-	settings.channels.push(Channel {
-		id: channel.id.clone(),
+	let channel = Channel {
+		id: channel.id,
 		name: channel.snippet.title,
 		icon: channel.snippet.thumbnails.medium.url,
 		uploads_playlist_id: channel.contentDetails.relatedPlaylists.uploads,
 		from_time: options.from_time,
 		refresh_rate_ms: options.refresh_rate_ms,
 		tags: options.tags,
-	});
-	data.save_settings()?;
-	data.user_history.push(Action::AddChannel(channel.id));
+		backend: None,
+		archive_rules: ArchiveRules::default(),
+		remind_minutes_before: None,
+		ignore_shorts: false,
+		title_filter: TitleFilter::default(),
+		notify_new_uploads: None,
+	};
+	let mut channels = data.channels.clone();
+	channels.push(channel.clone());
+	data.save_channels(channels).await?;
+	data.user_history.push(Action::AddChannel(channel)).await;
 	Ok(())
 }
 
@@ -289,10 +361,16 @@ pub async fn set_general_settings(
 	check_in_background: bool,
 	no_window_decorations: bool,
 	data: DataState<'_>,
-) -> Result<(), String> {
+) -> Result<(), KadiumError> {
 	let mut data = data.0.lock().await;
+	let previous_key = data.settings_ref().api_key_or_default();
 	data.settings().set_api_key(api_key);
+	if data.settings_ref().api_key_or_default() != previous_key {
+		// a different key has its own quota
+		data.backoff.reset();
+	}
 	data.settings().max_concurrent_requests = max_concurrent_requests;
+	data.limiter = RequestLimiter::new(max_concurrent_requests);
 	data.settings().check_in_background = check_in_background;
 	data.settings().no_window_decorations = no_window_decorations;
 	app_handle
@@ -311,42 +389,3 @@ impl ArcData {
 		Self(Arc::new(Mutex::new(data)))
 	}
 }
-
-#[derive(Serialize, Clone, Type)]
-pub struct UndoHistory {
-	pub entries: Vec<(u32, Action)>,
-}
-
-impl UndoHistory {
-	pub fn new() -> Self {
-		Self { entries: vec![] }
-	}
-	pub fn push(&mut self, action: Action) {
-		let time: u32 = SystemTime::now()
-			.duration_since(UNIX_EPOCH)
-			.unwrap()
-			.as_secs()
-			.try_into()
-			.unwrap();
-		self.entries.push((time, action));
-		if self.entries.len() > 100 {
-			self.entries.remove(0);
-		}
-	}
-}
-
-#[derive(Serialize, Clone, Type)]
-pub enum Action {
-	CheckNow,
-	Archive(String),
-	Unarchive(String),
-	AddChannel(String),
-	UpdateOrDeleteChannels(String),
-}
-
-#[command]
-#[specta::specta]
-pub async fn get_history(data: DataState<'_>) -> Result<UndoHistory, String> {
-	let data = data.0.lock().await;
-	Ok(data.user_history.clone())
-}
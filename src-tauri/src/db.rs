@@ -1,6 +1,7 @@
-use crate::api::playlist_items;
-use crate::data::{Action, AppPaths, DataState};
-use crate::throw;
+use crate::data::{AppPaths, DataState};
+use crate::error::{ErrorKind, KadiumError};
+use crate::history::Action;
+use crate::search;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use sqlx::migrate::MigrateDatabase;
@@ -8,66 +9,85 @@ use sqlx::sqlite::{SqliteConnectOptions, SqliteRow};
 use sqlx::{Row, Sqlite, SqlitePool};
 use tauri::command;
 
-pub async fn init(app_paths: &AppPaths) -> Result<SqlitePool, String> {
+pub async fn init(app_paths: &AppPaths) -> Result<SqlitePool, KadiumError> {
 	let exists = match Sqlite::database_exists(&app_paths.db).await {
 		Ok(exists) => exists,
-		Err(e) => throw!("Could not check if database exists: {}", e),
+		Err(e) => {
+			return Err(KadiumError::database(
+				"Could not check if database exists",
+				e,
+			))
+		}
 	};
 	if !exists {
 		if let Err(e) = std::fs::create_dir_all(&app_paths.app_dir) {
-			throw!("Error creating parent folder: {}", e.to_string());
+			return Err(KadiumError::file("Error creating parent folder", e));
 		}
 		match Sqlite::create_database(&app_paths.db).await {
 			Ok(_) => {}
-			Err(e) => throw!("Could not create database: {}", e),
+			Err(e) => return Err(KadiumError::database("Could not create database", e)),
 		}
 	}
 
 	let connect_options = SqliteConnectOptions::new().filename(&app_paths.db);
 	let pool = match SqlitePool::connect_with(connect_options).await {
 		Ok(pool) => pool,
-		Err(e) => throw!("Could not open database: {}", e),
+		Err(e) => return Err(KadiumError::database("Could not open database", e)),
 	};
 
 	match sqlx::migrate!("./migrations").run(&pool).await {
 		Ok(_) => {}
-		Err(e) => throw!("Could not run database migrations: {}", e),
+		Err(e) => {
+			return Err(KadiumError::database(
+				"Could not run database migrations",
+				e,
+			))
+		}
 	};
 
 	Ok(pool)
 }
 
-pub async fn get_ids(
-	videos: &Vec<playlist_items::Playlist>,
-	pool: &SqlitePool,
-) -> Result<Vec<String>, String> {
+/// Returns which of the IDs are already saved or ignored
+pub async fn get_ids(ids: &[String], pool: &SqlitePool) -> Result<Vec<String>, KadiumError> {
+	if ids.is_empty() {
+		return Ok(Vec::new());
+	}
 	// let mut id_placeholders = "\"?\"".to_string();
 	let mut id_placeholders = "?".to_string();
-	for _n in 0..(videos.len() - 1) {
+	for _n in 0..(ids.len() - 1) {
 		// id_placeholders.push_str(",\"?\"");
 		id_placeholders.push_str(",?");
 	}
 
-	let query_str = format!("SELECT id FROM videos WHERE id IN ({});", id_placeholders);
+	let query_str = format!(
+		"SELECT id FROM videos WHERE id IN ({0}) UNION SELECT id FROM ignoredVideos WHERE id IN ({0});",
+		id_placeholders
+	);
 	let mut query = sqlx::query(&query_str);
-	for video in videos {
-		query = query.bind(&video.contentDetails.videoId);
+	for id in ids.iter().chain(ids.iter()) {
+		query = query.bind(id);
 	}
 	let rows = match query.fetch_all(pool).await {
 		Ok(rows) => rows,
-		Err(e) => throw!("Unable to get video IDs: {}", e),
+		Err(e) => return Err(KadiumError::database("Unable to get video IDs", e)),
 	};
 	let mut existing_ids: Vec<String> = Vec::new();
 	for row in rows {
 		match row.try_get(0) {
 			Ok(id) => existing_ids.push(id),
-			Err(e) => throw!("Unable to get video ID from database row: {}", e),
+			Err(e) => {
+				return Err(KadiumError::database(
+					"Unable to get video ID from database row",
+					e,
+				))
+			}
 		};
 	}
 	Ok(existing_ids)
 }
 
-#[derive(Debug, Serialize, Type)]
+#[derive(Debug, Clone, Serialize, Type)]
 #[allow(non_snake_case)]
 pub struct Video {
 	pub id: String,
@@ -84,6 +104,11 @@ pub struct Video {
 	pub channelName: String,
 	pub unread: bool,
 	pub archived: bool,
+	pub liveState: LiveState,
+	/// Only known for livestreams and premieres
+	#[specta(type = Option<i32>)] // tauri bigint fix
+	pub scheduledStartMs: Option<i64>,
+	pub isShort: bool,
 }
 impl sqlx::FromRow<'_, SqliteRow> for Video {
 	fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
@@ -100,14 +125,47 @@ impl sqlx::FromRow<'_, SqliteRow> for Video {
 			channelName: row.try_get("channelName")?,
 			unread: row.try_get("unread")?,
 			archived: row.try_get("archived")?,
+			liveState: LiveState::parse(row.try_get("liveState")?),
+			scheduledStartMs: row.try_get("scheduledStartMs")?,
+			isShort: row.try_get("isShort")?,
 		})
 	}
 }
 
-pub async fn insert_video(video: &Video, pool: &SqlitePool) -> Result<(), String> {
+/// Livestreams and premieres. Upcoming and live ones are checked again until
+/// they end
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Type)]
+pub enum LiveState {
+	/// A regular upload
+	None,
+	Upcoming,
+	Live,
+	/// The livestream or premiere has ended
+	Replay,
+}
+impl LiveState {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			LiveState::None => "None",
+			LiveState::Upcoming => "Upcoming",
+			LiveState::Live => "Live",
+			LiveState::Replay => "Replay",
+		}
+	}
+	fn parse(value: &str) -> Self {
+		match value {
+			"Upcoming" => LiveState::Upcoming,
+			"Live" => LiveState::Live,
+			"Replay" => LiveState::Replay,
+			_ => LiveState::None,
+		}
+	}
+}
+
+pub async fn insert_video(video: &Video, pool: &SqlitePool) -> Result<(), KadiumError> {
 	let query_str =
-		"INSERT INTO videos (id,title,description,publishTimeMs,durationMs,thumbnailStandard,thumbnailMaxres,channelId,channelName) \
-		VALUES (?,?,?,?,?,?,?,?,?)";
+		"INSERT INTO videos (id,title,description,publishTimeMs,durationMs,thumbnailStandard,thumbnailMaxres,channelId,unread,archived,liveState,scheduledStartMs,isShort) \
+		VALUES (?,?,?,?,?,?,?,?,?,?,?,?,?)";
 	let query = sqlx::query(query_str)
 		.bind(&video.id)
 		.bind(&video.title)
@@ -117,23 +175,112 @@ pub async fn insert_video(video: &Video, pool: &SqlitePool) -> Result<(), String
 		.bind(video.thumbnailStandard)
 		.bind(video.thumbnailMaxres)
 		.bind(&video.channelId)
-		.bind(&video.channelName);
+		.bind(video.unread)
+		.bind(video.archived)
+		.bind(video.liveState.as_str())
+		.bind(video.scheduledStartMs)
+		.bind(video.isShort);
 	let rows_affected = match query.execute(pool).await {
 		Ok(result_rows) => result_rows.rows_affected(),
-		Err(e) => throw!("Error saving video: {}", e),
+		Err(e) => return Err(KadiumError::database("Error saving video", e)),
 	};
 	if rows_affected != 1 {
-		throw!("Error saving video: {} rows affected", rows_affected);
+		return Err(KadiumError::database(
+			"Error saving video",
+			format!("{} rows affected", rows_affected),
+		));
 	}
 	Ok(())
 }
 
+/// Remembers a video that was skipped, so `get_ids` treats it as known
+pub async fn ignore_video(id: &str, pool: &SqlitePool) -> Result<(), KadiumError> {
+	match sqlx::query("INSERT OR IGNORE INTO ignoredVideos (id) VALUES (?)")
+		.bind(id)
+		.execute(pool)
+		.await
+	{
+		Ok(_) => Ok(()),
+		Err(e) => Err(KadiumError::database("Error saving ignored video", e)),
+	}
+}
+
+/// Updates what can change while a livestream is upcoming or live. Keeps the
+/// unread and archived state
+pub async fn update_live_video(video: &Video, pool: &SqlitePool) -> Result<(), KadiumError> {
+	let query_str =
+		"UPDATE videos SET title = ?, description = ?, publishTimeMs = ?, durationMs = ?, \
+		thumbnailStandard = ?, thumbnailMaxres = ?, liveState = ?, scheduledStartMs = ? WHERE id = ?";
+	let query = sqlx::query(query_str)
+		.bind(&video.title)
+		.bind(&video.description)
+		.bind(video.publishTimeMs)
+		.bind(video.durationMs)
+		.bind(video.thumbnailStandard)
+		.bind(video.thumbnailMaxres)
+		.bind(video.liveState.as_str())
+		.bind(video.scheduledStartMs)
+		.bind(&video.id);
+	match query.execute(pool).await {
+		Ok(_) => Ok(()),
+		Err(e) => Err(KadiumError::database("Error updating video", e)),
+	}
+}
+
+/// Upcoming streams that are cancelled become private or get deleted
+pub async fn delete_video(id: &str, pool: &SqlitePool) -> Result<(), KadiumError> {
+	match sqlx::query("DELETE FROM videos WHERE id = ?")
+		.bind(id)
+		.execute(pool)
+		.await
+	{
+		Ok(_) => Ok(()),
+		Err(e) => Err(KadiumError::database("Error deleting video", e)),
+	}
+}
+
+/// Videos of the channel that are upcoming or live
+pub async fn get_live_ids(channel_id: &str, pool: &SqlitePool) -> Result<Vec<String>, KadiumError> {
+	let query = sqlx::query_scalar(
+		"SELECT id FROM videos WHERE channelId = ? AND liveState IN ('Upcoming', 'Live')",
+	)
+	.bind(channel_id);
+	match query.fetch_all(pool).await {
+		Ok(ids) => Ok(ids),
+		Err(e) => Err(KadiumError::database("Unable to get livestream IDs", e)),
+	}
+}
+
 #[derive(Debug, Serialize, Deserialize, Type)]
 pub struct Options {
 	show_all: bool,
 	show_archived: bool,
 	channel_filter: String,
 	tag: Option<String>,
+	#[serde(default)]
+	unread_only: bool,
+	/// Searches titles and descriptions. Supports `"phrases"`, `prefix*`,
+	/// `channel:name` and `tag:name`
+	#[serde(default)]
+	query: String,
+	#[serde(default)]
+	min_duration_ms: Option<u32>,
+	#[serde(default)]
+	max_duration_ms: Option<u32>,
+	#[specta(type = Option<i32>)] // tauri bigint fix
+	#[serde(default)]
+	published_after_ms: Option<i64>,
+	#[specta(type = Option<i32>)] // tauri bigint fix
+	#[serde(default)]
+	published_before_ms: Option<i64>,
+	/// Hides videos detected as Shorts when they were saved
+	#[serde(default)]
+	hide_shorts: bool,
+	#[serde(default)]
+	only_shorts: bool,
+	/// Only livestreams and premieres that have ended
+	#[serde(default)]
+	only_live_replays: bool,
 	limit: u16,
 }
 #[derive(Debug, Serialize, Deserialize, Type)]
@@ -144,104 +291,379 @@ pub struct After {
 	id: String,
 }
 
+/// WHERE conditions and their bindings for the filters in `Options`
+struct Filter {
+	wheres: Vec<String>,
+	bindings: Vec<String>,
+}
+impl Filter {
+	fn new(options: &Options) -> Self {
+		let mut filter = Filter {
+			wheres: Vec::new(),
+			bindings: Vec::new(),
+		};
+		if options.channel_filter != "" {
+			filter.push_channel_name(&options.channel_filter);
+		}
+		if !options.show_all {
+			if options.show_archived {
+				filter.push("archived = 1");
+			} else {
+				filter.push("archived = 0");
+			}
+		}
+		if options.unread_only {
+			filter.push("unread = 1");
+		}
+		if let Some(tag) = &options.tag {
+			filter.push_tag(tag);
+		}
+		if let Some(min_duration_ms) = options.min_duration_ms {
+			filter.push("durationMs >= ?");
+			filter.bindings.push(min_duration_ms.to_string());
+		}
+		if let Some(max_duration_ms) = options.max_duration_ms {
+			filter.push("durationMs <= ?");
+			filter.bindings.push(max_duration_ms.to_string());
+		}
+		if let Some(published_after_ms) = options.published_after_ms {
+			filter.push("publishTimeMs >= ?");
+			filter.bindings.push(published_after_ms.to_string());
+		}
+		if let Some(published_before_ms) = options.published_before_ms {
+			filter.push("publishTimeMs < ?");
+			filter.bindings.push(published_before_ms.to_string());
+		}
+		if options.hide_shorts {
+			filter.push("isShort = 0");
+		}
+		if options.only_shorts {
+			filter.push("isShort = 1");
+		}
+		if options.only_live_replays {
+			filter.push("liveState = 'Replay'");
+		}
+		let search = search::parse(&options.query);
+		if let Some(text) = search.text {
+			filter.push("id IN (SELECT id FROM videosSearch WHERE videosSearch MATCH ?)");
+			filter.bindings.push(text);
+		}
+		for channel_name in &search.channels {
+			filter.push_channel_name(channel_name);
+		}
+		for tag in &search.tags {
+			filter.push_tag(tag);
+		}
+		filter
+	}
+	fn push(&mut self, condition: &str) {
+		self.wheres.push(condition.to_string());
+	}
+	fn push_channel_name(&mut self, channel_name: &str) {
+		self.push("INSTR(LOWER(channelName), LOWER(?)) > 0");
+		self.bindings.push(channel_name.to_string());
+	}
+	fn push_tag(&mut self, tag: &str) {
+		self.push("channelId IN (SELECT channelId FROM channelTags WHERE tag = ?)");
+		self.bindings.push(tag.to_string());
+	}
+	/// Empty if there are no conditions
+	fn where_clause(&self) -> String {
+		if self.wheres.is_empty() {
+			String::new()
+		} else {
+			" WHERE ".to_owned() + &self.wheres.join(" AND ")
+		}
+	}
+}
+
 #[command]
 #[specta::specta]
 pub async fn get_videos(
 	options: Options,
 	after: Option<After>,
 	data: DataState<'_>,
-) -> Result<Vec<Video>, String> {
+) -> Result<Vec<Video>, KadiumError> {
 	let data = data.0.lock().await;
-	let mut selects: Vec<&str> = vec!["*"];
-	let mut wheres: Vec<&str> = Vec::new();
-	let mut bindings: Vec<&str> = Vec::new();
-	if options.channel_filter != "" {
-		selects.push("INSTR(LOWER(channelName), LOWER(?)) channelFilter");
-		wheres.push("channelFilter > 0");
-		bindings.push(&options.channel_filter);
-	}
-	if !options.show_all {
-		if options.show_archived {
-			wheres.push("archived = 1");
-		} else {
-			wheres.push("archived = 0");
-		}
-	}
-	let after_publish_time_ms;
-	if let Some(after) = &after {
-		wheres.push("(publishTimeMs,id) < (?,?)");
-		after_publish_time_ms = after.publishTimeMs.to_string();
-		bindings.push(&after_publish_time_ms);
-		bindings.push(&after.id);
-	}
-
-	let q;
-	if let Some(tag) = &options.tag {
-		let mut question_marks: Vec<&str> = Vec::new();
-		for channel in &data.settings_ref().channels {
-			if channel.tags.contains(tag) {
-				bindings.push(&channel.id);
-				question_marks.push("?");
-			}
-		}
-		q = format!("channelId IN ({})", question_marks.join(","));
-		wheres.push(&q);
+	let mut filter = Filter::new(&options);
+	if let Some(after) = after {
+		filter.push("(publishTimeMs,id) < (?,?)");
+		filter.bindings.push(after.publishTimeMs.to_string());
+		filter.bindings.push(after.id);
 	}
 
-	let mut query_str = "SELECT ".to_owned() + &selects.join(",");
-	query_str.push_str(" FROM videos");
-	if !wheres.is_empty() {
-		query_str.push_str(" WHERE ");
-		query_str.push_str(&wheres.join(" AND "));
-	}
+	let mut query_str = "SELECT * FROM videosWithChannel".to_owned();
+	query_str.push_str(&filter.where_clause());
 	query_str.push_str(" ORDER BY publishTimeMs DESC, id DESC");
 	query_str.push_str(&format!(" LIMIT {}", options.limit));
 
 	let mut query = sqlx::query_as(&query_str);
-	for binding in bindings {
+	for binding in filter.bindings {
 		query = query.bind(binding);
 	}
 	let videos: Vec<Video> = match query.fetch_all(&data.db_pool).await {
 		Ok(videos) => videos,
-		Err(e) => throw!("Error getting videos: {}", e),
+		Err(e) => return Err(KadiumError::database("Error getting videos", e)),
 	};
 	Ok(videos)
 }
 
-async fn set_archived(pool: &SqlitePool, id: &str, value: bool) -> Result<(), String> {
-	let query = sqlx::query("UPDATE videos SET archived = ? WHERE id = ?")
+/// Videos the user unarchived are remembered, so archive rules don't archive
+/// them again. Undo and redo pass `by_user: false`
+pub async fn set_archived(
+	pool: &SqlitePool,
+	id: &str,
+	value: bool,
+	by_user: bool,
+) -> Result<(), KadiumError> {
+	let query_str =
+		"UPDATE videos SET archived = ?, userUnarchived = userUnarchived OR ? WHERE id = ?";
+	let query = sqlx::query(query_str)
 		.bind(value)
+		.bind(by_user && !value)
 		.bind(id);
 	let rows_affected = match query.execute(pool).await {
 		Ok(result_rows) => result_rows.rows_affected(),
-		Err(e) => throw!("{}", e),
+		Err(e) => return Err(KadiumError::new(ErrorKind::Database, e.to_string())),
 	};
 	if rows_affected != 1 {
-		throw!("{} rows affected", rows_affected);
+		return Err(KadiumError::new(
+			ErrorKind::Database,
+			format!("{} rows affected", rows_affected),
+		));
 	}
 	Ok(())
 }
 
 #[command]
 #[specta::specta]
-pub async fn archive(id: String, data: DataState<'_>) -> Result<(), String> {
+pub async fn archive(id: String, data: DataState<'_>) -> Result<(), KadiumError> {
 	let mut data = data.0.lock().await;
-	match set_archived(&data.db_pool, &id, true).await {
-		Ok(()) => (),
-		Err(e) => throw!("Error archiving video: {}", e),
+	set_archived(&data.db_pool, &id, true, true)
+		.await
+		.map_err(|e| e.context("Error archiving video"))?;
+	data.user_history.push(Action::Archive(id)).await;
+	Ok(())
+}
+
+#[command]
+#[specta::specta]
+pub async fn unarchive(id: String, data: DataState<'_>) -> Result<(), KadiumError> {
+	let mut data = data.0.lock().await;
+	set_archived(&data.db_pool, &id, false, true)
+		.await
+		.map_err(|e| e.context("Error unarchiving video"))?;
+	data.user_history.push(Action::Unarchive(id)).await;
+	Ok(())
+}
+
+/// Stays well below SQLite's limit on variables per statement
+const MAX_IDS_PER_STATEMENT: usize = 500;
+
+/// Returns the IDs that were changed, so undoing leaves alone the videos that
+/// already had the value. `by_user` is like in `set_archived`
+pub async fn set_archived_many(
+	pool: &SqlitePool,
+	ids: &[String],
+	value: bool,
+	by_user: bool,
+) -> Result<Vec<String>, KadiumError> {
+	let mut tx = match pool.begin().await {
+		Ok(tx) => tx,
+		Err(e) => return Err(KadiumError::new(ErrorKind::Database, e.to_string())),
+	};
+	let mut changed_ids = Vec::new();
+	for chunk in ids.chunks(MAX_IDS_PER_STATEMENT) {
+		let query_str = format!(
+			"UPDATE videos SET archived = ?, userUnarchived = userUnarchived OR ? \
+			WHERE archived != ? AND id IN ({}) RETURNING id",
+			vec!["?"; chunk.len()].join(",")
+		);
+		let mut query = sqlx::query_scalar::<_, String>(&query_str)
+			.bind(value)
+			.bind(by_user && !value)
+			.bind(value);
+		for id in chunk {
+			query = query.bind(id);
+		}
+		match query.fetch_all(&mut *tx).await {
+			Ok(ids) => changed_ids.extend(ids),
+			Err(e) => return Err(KadiumError::new(ErrorKind::Database, e.to_string())),
+		}
+	}
+	if let Err(e) = tx.commit().await {
+		return Err(KadiumError::new(ErrorKind::Database, e.to_string()));
 	}
-	data.user_history.push(Action::Archive(id));
+	Ok(changed_ids)
+}
+
+/// Returns how many videos were archived
+#[command]
+#[specta::specta]
+pub async fn archive_many(ids: Vec<String>, data: DataState<'_>) -> Result<u32, KadiumError> {
+	let mut data = data.0.lock().await;
+	let archived_ids = set_archived_many(&data.db_pool, &ids, true, true)
+		.await
+		.map_err(|e| e.context("Error archiving videos"))?;
+	let count = archived_ids.len() as u32;
+	if !archived_ids.is_empty() {
+		data.user_history
+			.push(Action::ArchiveMany(archived_ids))
+			.await;
+	}
+	Ok(count)
+}
+
+/// Returns how many videos were unarchived
+#[command]
+#[specta::specta]
+pub async fn unarchive_many(ids: Vec<String>, data: DataState<'_>) -> Result<u32, KadiumError> {
+	let mut data = data.0.lock().await;
+	let unarchived_ids = set_archived_many(&data.db_pool, &ids, false, true)
+		.await
+		.map_err(|e| e.context("Error unarchiving videos"))?;
+	let count = unarchived_ids.len() as u32;
+	if !unarchived_ids.is_empty() {
+		data.user_history
+			.push(Action::UnarchiveMany(unarchived_ids))
+			.await;
+	}
+	Ok(count)
+}
+
+/// Archives every video `get_videos` would return with these options,
+/// ignoring the limit. Returns how many videos were archived
+#[command]
+#[specta::specta]
+pub async fn archive_matching(options: Options, data: DataState<'_>) -> Result<u32, KadiumError> {
+	let mut data = data.0.lock().await;
+	let mut filter = Filter::new(&options);
+	filter.push("archived = 0");
+	// The filter may refer to the channel name, which only the view has
+	let query_str = format!(
+		"UPDATE videos SET archived = 1 WHERE id IN (SELECT id FROM videosWithChannel{}) RETURNING id",
+		filter.where_clause()
+	);
+	let mut query = sqlx::query_scalar::<_, String>(&query_str);
+	for binding in filter.bindings {
+		query = query.bind(binding);
+	}
+	// A single statement runs in its own transaction
+	let archived_ids = match query.fetch_all(&data.db_pool).await {
+		Ok(ids) => ids,
+		Err(e) => return Err(KadiumError::database("Error archiving videos", e)),
+	};
+	let count = archived_ids.len() as u32;
+	if !archived_ids.is_empty() {
+		data.user_history
+			.push(Action::ArchiveMany(archived_ids))
+			.await;
+	}
+	Ok(count)
+}
+
+pub async fn set_unread(pool: &SqlitePool, id: &str, value: bool) -> Result<(), KadiumError> {
+	let query = sqlx::query("UPDATE videos SET unread = ? WHERE id = ?")
+		.bind(value)
+		.bind(id);
+	let rows_affected = match query.execute(pool).await {
+		Ok(result_rows) => result_rows.rows_affected(),
+		Err(e) => return Err(KadiumError::new(ErrorKind::Database, e.to_string())),
+	};
+	if rows_affected != 1 {
+		return Err(KadiumError::new(
+			ErrorKind::Database,
+			format!("{} rows affected", rows_affected),
+		));
+	}
+	Ok(())
+}
+
+pub async fn set_unread_many(
+	pool: &SqlitePool,
+	ids: &[String],
+	value: bool,
+) -> Result<(), KadiumError> {
+	let mut tx = match pool.begin().await {
+		Ok(tx) => tx,
+		Err(e) => return Err(KadiumError::new(ErrorKind::Database, e.to_string())),
+	};
+	for chunk in ids.chunks(MAX_IDS_PER_STATEMENT) {
+		let query_str = format!(
+			"UPDATE videos SET unread = ? WHERE id IN ({})",
+			vec!["?"; chunk.len()].join(",")
+		);
+		let mut query = sqlx::query(&query_str).bind(value);
+		for id in chunk {
+			query = query.bind(id);
+		}
+		if let Err(e) = query.execute(&mut *tx).await {
+			return Err(KadiumError::new(ErrorKind::Database, e.to_string()));
+		}
+	}
+	if let Err(e) = tx.commit().await {
+		return Err(KadiumError::new(ErrorKind::Database, e.to_string()));
+	}
+	Ok(())
+}
+
+#[command]
+#[specta::specta]
+pub async fn mark_read(id: String, data: DataState<'_>) -> Result<(), KadiumError> {
+	let mut data = data.0.lock().await;
+	set_unread(&data.db_pool, &id, false)
+		.await
+		.map_err(|e| e.context("Error marking video as read"))?;
+	data.user_history.push(Action::MarkRead(id)).await;
 	Ok(())
 }
 
 #[command]
 #[specta::specta]
-pub async fn unarchive(id: String, data: DataState<'_>) -> Result<(), String> {
+pub async fn mark_unread(id: String, data: DataState<'_>) -> Result<(), KadiumError> {
+	let mut data = data.0.lock().await;
+	set_unread(&data.db_pool, &id, true)
+		.await
+		.map_err(|e| e.context("Error marking video as unread"))?;
+	data.user_history.push(Action::MarkUnread(id)).await;
+	Ok(())
+}
+
+/// Marks every unread video as read, or only the ones from a channel or tag.
+/// The history entry lists the affected videos so it can be reverted.
+#[command]
+#[specta::specta]
+pub async fn mark_all_read(
+	channel_id: Option<String>,
+	tag: Option<String>,
+	data: DataState<'_>,
+) -> Result<(), KadiumError> {
 	let mut data = data.0.lock().await;
-	match set_archived(&data.db_pool, &id, false).await {
-		Ok(()) => (),
-		Err(e) => throw!("Error unarchiving video: {}", e),
+	let mut wheres: Vec<&str> = vec!["unread = 1"];
+	let mut bindings: Vec<&str> = Vec::new();
+	if let Some(channel_id) = &channel_id {
+		wheres.push("channelId = ?");
+		bindings.push(channel_id);
+	}
+	if let Some(tag) = &tag {
+		wheres.push("channelId IN (SELECT channelId FROM channelTags WHERE tag = ?)");
+		bindings.push(tag);
+	}
+
+	let query_str = format!(
+		"UPDATE videos SET unread = 0 WHERE {} RETURNING id",
+		wheres.join(" AND ")
+	);
+	let mut query = sqlx::query_scalar(&query_str);
+	for binding in bindings {
+		query = query.bind(binding);
+	}
+	let ids: Vec<String> = match query.fetch_all(&data.db_pool).await {
+		Ok(ids) => ids,
+		Err(e) => return Err(KadiumError::database("Error marking videos as read", e)),
+	};
+	if !ids.is_empty() {
+		data.user_history.push(Action::MarkAllRead(ids)).await;
 	}
-	data.user_history.push(Action::Unarchive(id));
 	Ok(())
 }
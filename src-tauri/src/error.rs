@@ -0,0 +1,79 @@
+use serde::Serialize;
+use specta::Type;
+use std::fmt;
+
+/// Lets the frontend react differently depending on what went wrong
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Type)]
+pub enum ErrorKind {
+	/// The daily quota of the API key is used up
+	QuotaExceeded,
+	RateLimited,
+	/// The API key is invalid or not allowed to use the API
+	ApiKey,
+	NotFound,
+	Network,
+	/// YouTube returned something we didn't expect
+	Parse,
+	/// YouTube had an internal error
+	Server,
+	/// Other errors returned by the YouTube API
+	Api,
+	ChannelExists,
+	InvalidUrl,
+	Database,
+	File,
+	Other,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct KadiumError {
+	pub kind: ErrorKind,
+	/// What we were doing when the error happened, like "Failed to get channel"
+	pub context: Option<String>,
+	pub message: String,
+}
+impl KadiumError {
+	pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+		Self {
+			kind,
+			context: None,
+			message: message.into(),
+		}
+	}
+	pub fn other(message: impl Into<String>) -> Self {
+		Self::new(ErrorKind::Other, message)
+	}
+	pub fn database(context: &str, e: impl fmt::Display) -> Self {
+		Self::new(ErrorKind::Database, e.to_string()).context(context)
+	}
+	pub fn file(context: &str, e: impl fmt::Display) -> Self {
+		Self::new(ErrorKind::File, e.to_string()).context(context)
+	}
+	/// Adds context in front of any existing context
+	pub fn context(mut self, context: impl Into<String>) -> Self {
+		let context = context.into();
+		self.context = match self.context {
+			Some(inner) => Some(format!("{}: {}", context, inner)),
+			None => Some(context),
+		};
+		self
+	}
+	pub fn is_quota(&self) -> bool {
+		self.kind == ErrorKind::QuotaExceeded
+	}
+	/// Errors that are likely to go away by themselves after a while
+	pub fn is_transient(&self) -> bool {
+		matches!(
+			self.kind,
+			ErrorKind::RateLimited | ErrorKind::Network | ErrorKind::Server
+		)
+	}
+}
+impl fmt::Display for KadiumError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.context {
+			Some(context) => write!(f, "{}: {}", context, self.message),
+			None => write!(f, "{}", self.message),
+		}
+	}
+}
@@ -0,0 +1,207 @@
+use crate::api::ApiClient;
+use crate::background::parse_datetime;
+use crate::db;
+use crate::error::{ErrorKind, KadiumError};
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// YouTube's Atom feeds cost no quota, but only include the latest 15 uploads
+/// and lack duration and livestream details
+pub async fn get(channel_id: &str, api: &ApiClient) -> Result<Vec<db::Video>, KadiumError> {
+	let _permit = api.limiter.acquire().await?;
+	let url = api.feed_base_url.trim_end_matches('/').to_string()
+		+ "/videos.xml?channel_id="
+		+ channel_id;
+	let response =
+		api.http.get(url).send().await.map_err(|e| {
+			KadiumError::new(ErrorKind::Network, format!("Feed request failed: {}", e))
+		})?;
+	let status = response.status();
+	if status == reqwest::StatusCode::NOT_FOUND {
+		return Err(KadiumError::new(
+			ErrorKind::NotFound,
+			"Channel feed not found",
+		));
+	} else if status.is_server_error() {
+		return Err(KadiumError::new(
+			ErrorKind::Server,
+			format!("Feed request failed: {}", status),
+		));
+	}
+	let xml = response
+		.text()
+		.await
+		.map_err(|e| KadiumError::new(ErrorKind::Network, format!("Feed request failed: {}", e)))?;
+	parse(&xml).map_err(|e| e.context("Failed to parse channel feed"))
+}
+
+#[derive(Default)]
+struct Entry {
+	video_id: Option<String>,
+	channel_id: Option<String>,
+	channel_name: Option<String>,
+	title: Option<String>,
+	description: Option<String>,
+	published: Option<String>,
+}
+impl Entry {
+	/// Duration and thumbnail sizes are unknown
+	fn into_video(self) -> Result<db::Video, KadiumError> {
+		let missing = |field: &str| {
+			KadiumError::new(ErrorKind::Parse, format!("Feed entry has no {}", field))
+		};
+		let published = self.published.ok_or_else(|| missing("published"))?;
+		Ok(db::Video {
+			id: self.video_id.ok_or_else(|| missing("yt:videoId"))?,
+			title: self.title.unwrap_or_default(),
+			description: self.description.unwrap_or_default(),
+			publishTimeMs: parse_datetime(&published)?.timestamp_millis(),
+			durationMs: 0,
+			thumbnailStandard: false,
+			thumbnailMaxres: false,
+			channelId: self.channel_id.ok_or_else(|| missing("yt:channelId"))?,
+			channelName: self.channel_name.unwrap_or_default(),
+			unread: true,
+			archived: false,
+			liveState: db::LiveState::None,
+			scheduledStartMs: None,
+			isShort: false,
+		})
+	}
+}
+
+fn parse_error(e: impl std::fmt::Display) -> KadiumError {
+	KadiumError::new(ErrorKind::Parse, e.to_string())
+}
+
+fn read_text(reader: &mut Reader<&[u8]>, start: &BytesStart) -> Result<String, KadiumError> {
+	let raw = reader.read_text(start.name()).map_err(parse_error)?;
+	let text = unescape(&raw).map_err(parse_error)?;
+	Ok(text.trim().to_string())
+}
+
+/// Documentation:
+/// https://developers.google.com/youtube/v3/guides/push_notifications
+pub fn parse(xml: &str) -> Result<Vec<db::Video>, KadiumError> {
+	let mut reader = Reader::from_str(xml);
+	let mut videos = Vec::new();
+	let mut entry: Option<Entry> = None;
+	loop {
+		match reader.read_event().map_err(parse_error)? {
+			Event::Start(start) => {
+				if start.name().as_ref() == b"entry" {
+					entry = Some(Entry::default());
+					continue;
+				}
+				let entry = match &mut entry {
+					Some(entry) => entry,
+					None => continue, // feed metadata
+				};
+				let field = match start.name().as_ref() {
+					b"yt:videoId" => &mut entry.video_id,
+					b"yt:channelId" => &mut entry.channel_id,
+					b"title" => &mut entry.title,
+					// <author><name>
+					b"name" => &mut entry.channel_name,
+					b"published" => &mut entry.published,
+					b"media:description" => &mut entry.description,
+					_ => continue,
+				};
+				*field = Some(read_text(&mut reader, &start)?);
+			}
+			Event::End(end) => {
+				if end.name().as_ref() == b"entry" {
+					if let Some(entry) = entry.take() {
+						videos.push(entry.into_video()?);
+					}
+				}
+			}
+			Event::Eof => break,
+			_ => {}
+		}
+	}
+	Ok(videos)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const FEED: &str = include_str!("../tests/fixtures/feed.xml");
+
+	#[test]
+	fn parses_entries() {
+		let videos = parse(FEED).unwrap();
+		// the feed's own title, author and channel ID are not an entry
+		assert_eq!(videos.len(), 2);
+
+		let video = &videos[0];
+		assert_eq!(video.id, "aH4b3sAs-l8");
+		assert_eq!(video.channelId, "UC9RM-iSvTu1uPJb8X5yp3EQ");
+		assert_eq!(video.channelName, "Wendover Productions");
+		assert_eq!(video.title, "Why Electric Planes are Inevitably Coming");
+		assert_eq!(
+			video.description,
+			"Planes & batteries: the \"inevitable\" future\nSecond line"
+		);
+		assert_eq!(video.publishTimeMs, 1623861277000);
+		assert_eq!(video.durationMs, 0);
+
+		let video = &videos[1];
+		assert_eq!(video.id, "KBlfW4QcpC4");
+		assert_eq!(video.title, "The News <Live> Q&A");
+		assert_eq!(video.description, "");
+	}
+
+	/// Serves the fixture once over plain HTTP, standing in for YouTube
+	async fn serve_feed() -> (String, tokio::task::JoinHandle<String>) {
+		use tokio::io::{AsyncReadExt, AsyncWriteExt};
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let base_url = format!("http://{}/feeds", listener.local_addr().unwrap());
+		let handle = tokio::spawn(async move {
+			let (mut stream, _) = listener.accept().await.unwrap();
+			let mut request = Vec::new();
+			let mut buf = [0; 1024];
+			while !request.ends_with(b"\r\n\r\n") {
+				let n = stream.read(&mut buf).await.unwrap();
+				assert!(n > 0, "connection closed before the request ended");
+				request.extend_from_slice(&buf[..n]);
+			}
+			let response = format!(
+				"HTTP/1.1 200 OK\r\nContent-Type: application/atom+xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+				FEED.len(),
+				FEED
+			);
+			stream.write_all(response.as_bytes()).await.unwrap();
+			let request = String::from_utf8(request).unwrap();
+			request.lines().next().unwrap().to_string()
+		});
+		(base_url, handle)
+	}
+
+	#[tokio::test]
+	async fn gets_feed_from_base_url() {
+		let (feed_base_url, server) = serve_feed().await;
+		let api = ApiClient {
+			key: String::new(),
+			base_url: String::new(),
+			feed_base_url,
+			limiter: crate::api::RequestLimiter::new(1),
+			pool: sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap(),
+			http: reqwest::Client::new(),
+		};
+		let videos = get("UC9RM-iSvTu1uPJb8X5yp3EQ", &api).await.unwrap();
+		assert_eq!(videos.len(), 2);
+		assert_eq!(
+			server.await.unwrap(),
+			"GET /feeds/videos.xml?channel_id=UC9RM-iSvTu1uPJb8X5yp3EQ HTTP/1.1"
+		);
+	}
+
+	#[test]
+	fn rejects_entries_without_video_id() {
+		let xml = FEED.replace("<yt:videoId>aH4b3sAs-l8</yt:videoId>", "");
+		assert!(parse(&xml).is_err());
+	}
+}
@@ -0,0 +1,332 @@
+use crate::data::{Data, DataState};
+use crate::db;
+use crate::error::KadiumError;
+use crate::settings::Channel;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, Row, SqlitePool};
+use std::convert::TryInto;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::command;
+
+/// How many entries are kept in memory for undo
+const UNDO_LIMIT: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Type)]
+pub enum Action {
+	CheckNow,
+	Archive(String),
+	Unarchive(String),
+	/// IDs of the videos that were archived
+	ArchiveMany(Vec<String>),
+	/// IDs of the videos that were unarchived
+	UnarchiveMany(Vec<String>),
+	MarkRead(String),
+	MarkUnread(String),
+	/// IDs of the videos that were marked as read
+	MarkAllRead(Vec<String>),
+	/// IDs of the videos archived by archive rules
+	AutoArchive(Vec<String>),
+	AddChannel(#[serde(deserialize_with = "crate::settings::deserialize_old_channel")] Channel),
+	UpdateOrDeleteChannels {
+		#[serde(deserialize_with = "crate::settings::deserialize_old_channels")]
+		previous: Vec<Channel>,
+		#[serde(deserialize_with = "crate::settings::deserialize_old_channels")]
+		new: Vec<Channel>,
+	},
+}
+
+impl Action {
+	fn is_undoable(&self) -> bool {
+		// Undoing an automatic archive would only last until the next check
+		!matches!(self, Action::CheckNow | Action::AutoArchive(_))
+	}
+	/// Performs the action again, or its inverse if `undo` is true
+	async fn apply(&self, data: &mut Data, undo: bool) -> Result<(), KadiumError> {
+		match self {
+			Action::CheckNow | Action::AutoArchive(_) => {}
+			Action::Archive(id) => db::set_archived(&data.db_pool, id, !undo, false).await?,
+			Action::Unarchive(id) => db::set_archived(&data.db_pool, id, undo, false).await?,
+			Action::ArchiveMany(ids) => {
+				db::set_archived_many(&data.db_pool, ids, !undo, false).await?;
+			}
+			Action::UnarchiveMany(ids) => {
+				db::set_archived_many(&data.db_pool, ids, undo, false).await?;
+			}
+			Action::MarkRead(id) => db::set_unread(&data.db_pool, id, undo).await?,
+			Action::MarkUnread(id) => db::set_unread(&data.db_pool, id, !undo).await?,
+			Action::MarkAllRead(ids) => db::set_unread_many(&data.db_pool, ids, undo).await?,
+			Action::AddChannel(channel) => {
+				let mut channels = data.channels.clone();
+				if undo {
+					channels.retain(|c| c.id != channel.id);
+				} else if channels.iter().all(|c| c.id != channel.id) {
+					channels.push(channel.clone());
+				}
+				data.save_channels(channels).await?;
+			}
+			Action::UpdateOrDeleteChannels { previous, new } => {
+				let channels = if undo { previous } else { new };
+				data.save_channels(channels.clone()).await?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Serialize, Clone, Type)]
+pub struct HistoryEntry {
+	#[specta(type = i32)] // tauri bigint fix
+	pub id: i64,
+	/// Unix time in seconds
+	pub time: u32,
+	pub action: Action,
+	pub undone: bool,
+}
+impl sqlx::FromRow<'_, SqliteRow> for HistoryEntry {
+	fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+		let action: String = row.try_get("action")?;
+		Ok(HistoryEntry {
+			id: row.try_get("id")?,
+			time: row.try_get("time")?,
+			action: serde_json::from_str(&action).map_err(|e| sqlx::Error::ColumnDecode {
+				index: "action".to_string(),
+				source: Box::new(e),
+			})?,
+			undone: row.try_get("undone")?,
+		})
+	}
+}
+
+/// Actions are saved to the database so the history survives restarts. The
+/// most recent ones are also kept in memory for undo and redo.
+pub struct UndoHistory {
+	pool: SqlitePool,
+	/// How many entries to keep in the database
+	retention: u32,
+	entries: Vec<HistoryEntry>,
+	/// Undone entries, the most recently undone last
+	redo_entries: Vec<HistoryEntry>,
+}
+
+fn now_secs() -> u32 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap()
+		.as_secs()
+		.try_into()
+		.unwrap()
+}
+
+/// History is not critical, so rows that can't be decoded, like ones with an
+/// action that was since renamed, are logged and skipped
+fn decode_entries(rows: Vec<SqliteRow>) -> Vec<HistoryEntry> {
+	let mut entries = Vec::new();
+	for row in rows {
+		match HistoryEntry::from_row(&row) {
+			Ok(entry) => entries.push(entry),
+			Err(e) => eprintln!("Skipping history entry: {}", e),
+		}
+	}
+	entries
+}
+
+impl UndoHistory {
+	pub async fn load(pool: &SqlitePool, retention: u32) -> Result<Self, KadiumError> {
+		let query_str = "SELECT * FROM history WHERE undone = 0 ORDER BY id DESC LIMIT ?";
+		let mut entries = match sqlx::query(query_str)
+			.bind(UNDO_LIMIT as u32)
+			.fetch_all(pool)
+			.await
+		{
+			Ok(rows) => decode_entries(rows),
+			Err(e) => return Err(KadiumError::database("Unable to load history", e)),
+		};
+		entries.reverse();
+		// Only the latest undoable entries can be undone, so the most recently
+		// undone entry is the oldest one
+		let query_str = "SELECT * FROM history WHERE undone = 1 ORDER BY id DESC";
+		let redo_entries = match sqlx::query(query_str).fetch_all(pool).await {
+			Ok(rows) => decode_entries(rows),
+			Err(e) => return Err(KadiumError::database("Unable to load history", e)),
+		};
+		let mut history = Self {
+			pool: pool.clone(),
+			retention,
+			entries,
+			redo_entries,
+		};
+		// the retention may have been lowered since the last prune
+		history.prune().await;
+		Ok(history)
+	}
+
+	/// Failing to save history should not fail the action itself, so errors
+	/// are only logged
+	pub async fn push(&mut self, action: Action) {
+		if action.is_undoable() && !self.redo_entries.is_empty() {
+			self.redo_entries.clear();
+			if let Err(e) = sqlx::query("DELETE FROM history WHERE undone = 1")
+				.execute(&self.pool)
+				.await
+			{
+				eprintln!("Unable to clear undone history: {}", e);
+			}
+		}
+		let time = now_secs();
+		let id = match insert(&self.pool, time, &action).await {
+			Ok(id) => id,
+			Err(e) => {
+				eprintln!("{}", e);
+				return;
+			}
+		};
+		self.entries.push(HistoryEntry {
+			id,
+			time,
+			action,
+			undone: false,
+		});
+		if self.entries.len() > UNDO_LIMIT {
+			self.entries.remove(0);
+		}
+		self.prune().await;
+	}
+
+	/// Also drops pruned entries from the undo and redo stacks, since there's
+	/// nothing left to undo or redo them with
+	async fn prune(&mut self) {
+		let query_str = "DELETE FROM history WHERE id NOT IN \
+			(SELECT id FROM history ORDER BY id DESC LIMIT ?) RETURNING id";
+		let pruned_ids = match sqlx::query_scalar::<_, i64>(query_str)
+			.bind(self.retention)
+			.fetch_all(&self.pool)
+			.await
+		{
+			Ok(ids) => ids,
+			Err(e) => {
+				eprintln!("Unable to prune history: {}", e);
+				return;
+			}
+		};
+		// the newest entries are kept, so everything pruned is older
+		if let Some(&newest_pruned) = pruned_ids.iter().max() {
+			self.entries.retain(|entry| entry.id > newest_pruned);
+			self.redo_entries.retain(|entry| entry.id > newest_pruned);
+		}
+	}
+
+	async fn set_undone(&self, id: i64, undone: bool) -> Result<(), KadiumError> {
+		let query = sqlx::query("UPDATE history SET undone = ? WHERE id = ?")
+			.bind(undone)
+			.bind(id);
+		match query.execute(&self.pool).await {
+			Ok(_) => Ok(()),
+			Err(e) => Err(KadiumError::database("Unable to update history", e)),
+		}
+	}
+}
+
+async fn insert(pool: &SqlitePool, time: u32, action: &Action) -> Result<i64, KadiumError> {
+	let json = match serde_json::to_string(action) {
+		Ok(json) => json,
+		Err(e) => return Err(KadiumError::other(e.to_string()).context("Unable to save history")),
+	};
+	let query = sqlx::query("INSERT INTO history (time, action) VALUES (?, ?)")
+		.bind(time)
+		.bind(json);
+	match query.execute(pool).await {
+		Ok(result) => Ok(result.last_insert_rowid()),
+		Err(e) => Err(KadiumError::database("Unable to save history", e)),
+	}
+}
+
+/// For actions taken in the background, which has no access to `Data`. Only
+/// actions that can't be undone should be recorded this way, since the entry
+/// is not added to the in-memory undo stack.
+pub async fn record(pool: &SqlitePool, action: Action) -> Result<(), KadiumError> {
+	insert(pool, now_secs(), &action).await?;
+	Ok(())
+}
+
+/// Newest entries first. Pass the ID of the last entry as `before_id` to get
+/// the next page
+#[command]
+#[specta::specta]
+pub async fn get_history(
+	limit: u32,
+	before_id: Option<u32>,
+	data: DataState<'_>,
+) -> Result<Vec<HistoryEntry>, KadiumError> {
+	let data = data.0.lock().await;
+	let query_str = "SELECT * FROM history WHERE id < ? ORDER BY id DESC LIMIT ?";
+	let mut before_id = before_id.map(i64::from).unwrap_or(i64::MAX);
+	let mut entries = Vec::new();
+	// Keep going if rows were skipped, so a short page still means the end
+	while entries.len() < limit as usize {
+		let query = sqlx::query(query_str)
+			.bind(before_id)
+			.bind(limit - entries.len() as u32);
+		let rows = match query.fetch_all(&data.db_pool).await {
+			Ok(rows) => rows,
+			Err(e) => return Err(KadiumError::database("Unable to get history", e)),
+		};
+		before_id = match rows.last() {
+			Some(row) => match row.try_get("id") {
+				Ok(id) => id,
+				Err(e) => return Err(KadiumError::database("Unable to get history", e)),
+			},
+			None => break,
+		};
+		entries.extend(decode_entries(rows));
+	}
+	Ok(entries)
+}
+
+/// Reverts the most recent undoable action. Returns the undone action
+#[command]
+#[specta::specta]
+pub async fn undo(data: DataState<'_>) -> Result<Option<Action>, KadiumError> {
+	let mut data = data.0.lock().await;
+	let index = match data
+		.user_history
+		.entries
+		.iter()
+		.rposition(|entry| entry.action.is_undoable())
+	{
+		Some(index) => index,
+		None => return Ok(None),
+	};
+	let action = data.user_history.entries[index].action.clone();
+	action
+		.apply(&mut data, true)
+		.await
+		.map_err(|e| e.context("Failed to undo"))?;
+	let mut entry = data.user_history.entries.remove(index);
+	data.user_history.set_undone(entry.id, true).await?;
+	entry.undone = true;
+	data.user_history.redo_entries.push(entry);
+	Ok(Some(action))
+}
+
+/// Performs the most recently undone action again. Returns the redone action
+#[command]
+#[specta::specta]
+pub async fn redo(data: DataState<'_>) -> Result<Option<Action>, KadiumError> {
+	let mut data = data.0.lock().await;
+	let action = match data.user_history.redo_entries.last() {
+		Some(entry) => entry.action.clone(),
+		None => return Ok(None),
+	};
+	action
+		.apply(&mut data, false)
+		.await
+		.map_err(|e| e.context("Failed to redo"))?;
+	if let Some(mut entry) = data.user_history.redo_entries.pop() {
+		data.user_history.set_undone(entry.id, false).await?;
+		entry.undone = false;
+		data.user_history.entries.push(entry);
+	}
+	Ok(Some(action))
+}
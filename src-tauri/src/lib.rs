@@ -3,17 +3,31 @@
 	windows_subsystem = "windows"
 )]
 
+use crate::api::RequestLimiter;
 use crate::data::{AppPaths, ArcData, Data};
+use crate::error::KadiumError;
+use crate::history::UndoHistory;
+use crate::notifications::NotificationQueue;
+use crate::reminders::ShownReminders;
 use crate::settings::VersionedSettings;
-use data::UndoHistory;
 use tauri::{command, Manager, WebviewUrl, WebviewWindowBuilder};
 use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 
 mod api;
+mod archive_rules;
 mod background;
+mod channels;
 mod data;
 mod db;
+mod error;
+mod feed;
+mod history;
+mod notifications;
+mod quota;
+mod reminders;
+mod search;
 mod settings;
+mod title_filter;
 
 fn error_popup_main_thread(msg: impl AsRef<str>) {
 	let msg = msg.as_ref().to_string();
@@ -25,13 +39,6 @@ fn error_popup_main_thread(msg: impl AsRef<str>) {
 	builder.show();
 }
 
-#[macro_export]
-macro_rules! throw {
-	($($arg:tt)*) => {{
-		return Err(format!($($arg)*))
-	}};
-}
-
 #[command]
 #[specta::specta]
 fn error_popup(app_handle: tauri::AppHandle, msg: String) {
@@ -46,7 +53,7 @@ fn error_popup(app_handle: tauri::AppHandle, msg: String) {
 }
 
 /// This can display dialogs, which needs to happen before tauri runs to not panic
-fn load_data(paths: &AppPaths) -> Result<VersionedSettings, String> {
+fn load_data(paths: &AppPaths) -> Result<VersionedSettings, KadiumError> {
 	if paths.settings_file.exists() {
 		return match settings::VersionedSettings::load(paths) {
 			Ok(settings) => Ok(settings),
@@ -63,15 +70,25 @@ pub async fn run() {
 		tauri_specta::Builder::<tauri::Wry>::new().commands(tauri_specta::collect_commands![
 			error_popup,
 			data::get_settings,
+			data::get_channels,
 			data::tags,
 			data::set_channels,
 			data::add_channel,
 			data::set_general_settings,
 			data::check_now,
-			data::get_history,
+			history::get_history,
+			history::undo,
+			history::redo,
 			db::get_videos,
 			db::archive,
-			db::unarchive
+			db::unarchive,
+			db::archive_many,
+			db::unarchive_many,
+			db::archive_matching,
+			db::mark_read,
+			db::mark_unread,
+			db::mark_all_read,
+			quota::get_quota_usage
 		]);
 
 	#[cfg(debug_assertions)]
@@ -91,7 +108,7 @@ pub async fn run() {
 	let mut settings = match load_data(&app_paths) {
 		Ok(v) => v,
 		Err(e) => {
-			error_popup_main_thread(&e);
+			error_popup_main_thread(e.to_string());
 			panic!("{}", e);
 		}
 	};
@@ -99,7 +116,41 @@ pub async fn run() {
 	let pool = match db::init(&app_paths).await {
 		Ok(pool) => pool,
 		Err(e) => {
-			error_popup_main_thread(&e);
+			error_popup_main_thread(e.to_string());
+			panic!("{}", e);
+		}
+	};
+
+	let channels_to_import = settings.unwrap().take_channels_to_import();
+	if !channels_to_import.is_empty() {
+		let imported = match channels::save_all(&channels_to_import, &pool).await {
+			Ok(()) => settings.save(&app_paths),
+			Err(e) => Err(e),
+		};
+		if let Err(e) = imported {
+			let e = e.context("Error importing channels");
+			error_popup_main_thread(e.to_string());
+			panic!("{}", e);
+		}
+	}
+	let channels = match channels::get_all(&pool).await {
+		Ok(channels) => channels,
+		Err(e) => {
+			error_popup_main_thread(e.to_string());
+			panic!("{}", e);
+		}
+	};
+
+	// before loading the history, so the archived videos are in it
+	if let Err(e) = archive_rules::apply_all(settings.unwrap_ref(), &channels, &pool).await {
+		eprintln!("{}", e);
+	}
+
+	let history_retention = settings.unwrap_ref().history_retention;
+	let user_history = match UndoHistory::load(&pool, history_retention).await {
+		Ok(user_history) => user_history,
+		Err(e) => {
+			error_popup_main_thread(e.to_string());
 			panic!("{}", e);
 		}
 	};
@@ -139,14 +190,30 @@ pub async fn run() {
 				}
 			}
 
-			let data = Data {
-				bg_handle: background::spawn_bg(settings.unwrap(), &pool, win.clone()),
+			let http_client = api::http_client_or_without_proxy(settings.unwrap_ref());
+			let mut data = Data {
+				bg_handle: None,
 				db_pool: pool,
+				limiter: RequestLimiter::new(settings.unwrap_ref().max_concurrent_requests),
+				http_client,
+				backoff: background::Backoff::new(),
+				notification_queue: NotificationQueue::new(),
+				shown_reminders: ShownReminders::new(),
 				versioned_settings: settings,
+				channels,
 				paths: app_paths,
 				window: win.clone(),
-				user_history: UndoHistory::new(),
+				user_history,
 			};
+			data.bg_handle = background::spawn_bg(
+				data.settings_ref(),
+				&data.channels,
+				data.api_client(),
+				&data.backoff,
+				&data.notification_queue,
+				&data.shown_reminders,
+				win.clone(),
+			);
 			app.manage(ArcData::new(data));
 
 			Ok(())
@@ -0,0 +1,436 @@
+use crate::db::{LiveState, Video};
+use crate::error::KadiumError;
+use crate::settings::{NotificationClick, NotificationSchedule, QuietHours, Settings};
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Weekday};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time;
+
+/// New videos waiting for quiet hours to end or for the next digest
+#[derive(Clone)]
+pub struct NotificationQueue(Arc<Mutex<Vec<Video>>>);
+impl Default for NotificationQueue {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl NotificationQueue {
+	pub fn new() -> Self {
+		Self(Arc::new(Mutex::new(Vec::new())))
+	}
+}
+
+/// Shows new video notifications, or queues them during quiet hours and in
+/// digest mode
+#[derive(Clone)]
+pub struct Notifier {
+	schedule: Arc<Schedule>,
+	queue: NotificationQueue,
+	click: NotificationClick,
+	archive_on_open: bool,
+	window: tauri::WebviewWindow,
+}
+impl Notifier {
+	pub fn new(
+		settings: &Settings,
+		queue: &NotificationQueue,
+		window: tauri::WebviewWindow,
+	) -> Self {
+		let schedule = match Schedule::parse(&settings.notification_schedule) {
+			Ok(schedule) => schedule,
+			Err(e) => {
+				eprintln!("Ignoring notification schedule: {}", e);
+				Schedule::default()
+			}
+		};
+		Self {
+			schedule: Arc::new(schedule),
+			queue: queue.clone(),
+			click: settings.notification_click,
+			archive_on_open: settings.archive_on_open,
+			window,
+		}
+	}
+	pub fn notify(&self, videos: Vec<Video>) {
+		if videos.is_empty() {
+			return;
+		}
+		if self.schedule.holds_back(Local::now().naive_local()) {
+			self.queue.0.lock().unwrap().extend(videos);
+		} else {
+			self.show(&videos);
+		}
+	}
+	/// Delivers the queue as a digest whenever the schedule allows
+	pub async fn run(&self) {
+		let mut interval = time::interval(Duration::from_secs(60));
+		interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+		let mut last_check = Local::now().naive_local();
+		loop {
+			interval.tick().await;
+			let now = Local::now().naive_local();
+			if self.schedule.is_delivery_time(last_check, now) {
+				let videos = std::mem::take(&mut *self.queue.0.lock().unwrap());
+				self.show(&videos);
+			}
+			last_check = now;
+		}
+	}
+}
+
+#[derive(Default)]
+struct Schedule {
+	quiet_hours: Option<ParsedQuietHours>,
+	digest_times: Vec<NaiveTime>,
+}
+struct ParsedQuietHours {
+	start: NaiveTime,
+	end: NaiveTime,
+	/// Every day if empty
+	weekdays: Vec<Weekday>,
+}
+impl ParsedQuietHours {
+	fn starts_on(&self, weekday: Weekday) -> bool {
+		self.weekdays.is_empty() || self.weekdays.contains(&weekday)
+	}
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, KadiumError> {
+	match NaiveTime::parse_from_str(value, "%H:%M") {
+		Ok(time) => Ok(time),
+		Err(e) => Err(KadiumError::other(format!("Invalid time {}: {}", value, e))),
+	}
+}
+
+impl Schedule {
+	fn parse(settings: &NotificationSchedule) -> Result<Self, KadiumError> {
+		let quiet_hours = match &settings.quiet_hours {
+			Some(QuietHours {
+				start,
+				end,
+				weekdays,
+			}) => {
+				let mut parsed_weekdays = Vec::new();
+				for weekday in weekdays {
+					match weekday.parse() {
+						Ok(weekday) => parsed_weekdays.push(weekday),
+						Err(_) => {
+							return Err(KadiumError::other(format!("Invalid weekday {}", weekday)))
+						}
+					}
+				}
+				Some(ParsedQuietHours {
+					start: parse_time(start)?,
+					end: parse_time(end)?,
+					weekdays: parsed_weekdays,
+				})
+			}
+			None => None,
+		};
+		let mut digest_times = Vec::new();
+		for time in &settings.digest_times {
+			digest_times.push(parse_time(time)?);
+		}
+		Ok(Schedule {
+			quiet_hours,
+			digest_times,
+		})
+	}
+	fn is_quiet(&self, now: NaiveDateTime) -> bool {
+		let quiet_hours = match &self.quiet_hours {
+			Some(quiet_hours) => quiet_hours,
+			None => return false,
+		};
+		let time = now.time();
+		let today = now.date().weekday();
+		if quiet_hours.start <= quiet_hours.end {
+			quiet_hours.starts_on(today) && time >= quiet_hours.start && time < quiet_hours.end
+		} else {
+			// the quiet hours span midnight
+			(quiet_hours.starts_on(today) && time >= quiet_hours.start)
+				|| (quiet_hours.starts_on(today.pred()) && time < quiet_hours.end)
+		}
+	}
+	fn holds_back(&self, now: NaiveDateTime) -> bool {
+		!self.digest_times.is_empty() || self.is_quiet(now)
+	}
+	/// Whether the queue should be delivered, given the time of the previous
+	/// check. Digest times during quiet hours are skipped
+	fn is_delivery_time(&self, last_check: NaiveDateTime, now: NaiveDateTime) -> bool {
+		if self.is_quiet(now) {
+			return false;
+		}
+		if self.digest_times.is_empty() {
+			return true;
+		}
+		self.digest_times.iter().any(|time| {
+			[last_check.date(), now.date()].iter().any(|date| {
+				let digest_time = date.and_time(*time);
+				last_check < digest_time && digest_time <= now
+			})
+		})
+	}
+}
+
+/// How many videos a summary notification lists by name
+const MAX_SUMMARY_TITLES: usize = 5;
+
+impl Notifier {
+	/// Shows one notification for a single video, or a summary for several,
+	/// regardless of the schedule
+	pub fn show(&self, videos: &[Video]) {
+		let (title, body) = match videos {
+			[] => return,
+			[video] => (
+				video.channelName.clone(),
+				format!("{}\n{}", video.title, video_status(video)),
+			),
+			videos => {
+				let mut lines: Vec<String> = videos
+					.iter()
+					.take(MAX_SUMMARY_TITLES)
+					.map(|video| format!("{}: {}", video.channelName, video.title))
+					.collect();
+				if videos.len() > MAX_SUMMARY_TITLES {
+					lines.push(format!("and {} more", videos.len() - MAX_SUMMARY_TITLES));
+				}
+				(format!("{} new videos", videos.len()), lines.join("\n"))
+			}
+		};
+		let video_id = match videos {
+			[video] => Some(video.id.clone()),
+			_ => None,
+		};
+		if let Err(e) = self.show_clickable(title, body, video_id) {
+			eprintln!("Unable to show new video notification: {}", e);
+		}
+	}
+}
+
+/// Each platform reports clicks differently, so notifications are shown
+/// without the notification plugin, which doesn't report them
+mod click {
+	use super::Notifier;
+	use crate::data::ArcData;
+	use crate::db;
+	use crate::history::Action;
+	use crate::settings::NotificationClick;
+	use tauri::{Emitter, Manager};
+	use tauri_plugin_opener::OpenerExt;
+
+	impl Notifier {
+		/// Summaries always show the app
+		pub(super) fn clicked(&self, video_id: Option<String>) {
+			match (video_id, self.click) {
+				(Some(id), NotificationClick::OpenVideo) => {
+					let url = "https://youtube.com/watch?v=".to_string() + &id;
+					if let Err(e) = self
+						.window
+						.app_handle()
+						.opener()
+						.open_url(url, None::<&str>)
+					{
+						eprintln!("Unable to open video: {}", e);
+						return;
+					}
+					if self.archive_on_open {
+						let window = self.window.clone();
+						tauri::async_runtime::spawn(async move {
+							archive_opened(&window, id).await;
+						});
+					}
+				}
+				(Some(id), NotificationClick::ShowApp) => {
+					self.show_window();
+					let _ = self.window.emit("selectVideo", id);
+				}
+				(None, _) => self.show_window(),
+			}
+		}
+
+		fn show_window(&self) {
+			let _ = self.window.unminimize();
+			let _ = self.window.show();
+			let _ = self.window.set_focus();
+		}
+	}
+
+	async fn archive_opened(window: &tauri::WebviewWindow, id: String) {
+		let data = window.app_handle().state::<ArcData>();
+		let mut data = data.0.lock().await;
+		match db::set_archived(&data.db_pool, &id, true, true).await {
+			Ok(()) => {
+				data.user_history.push(Action::Archive(id)).await;
+				let _ = window.emit("refresh", "");
+			}
+			Err(e) => eprintln!("Error archiving opened video: {}", e),
+		}
+	}
+}
+
+/// How many notifications can wait for a click at once. Linux and macOS block
+/// a thread per notification until it's clicked or dismissed, and
+/// notifications kept in a tray may never be
+#[cfg(unix)]
+const MAX_WAITING: usize = 10;
+#[cfg(unix)]
+static WAITING: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Held by a thread waiting for a click. Once all are taken, notifications
+/// are shown without waiting, so clicking them does nothing
+#[cfg(unix)]
+struct WaitSlot;
+#[cfg(unix)]
+impl WaitSlot {
+	fn take() -> Option<Self> {
+		use std::sync::atomic::Ordering;
+		if WAITING.fetch_add(1, Ordering::SeqCst) < MAX_WAITING {
+			Some(WaitSlot)
+		} else {
+			WAITING.fetch_sub(1, Ordering::SeqCst);
+			None
+		}
+	}
+}
+#[cfg(unix)]
+impl Drop for WaitSlot {
+	fn drop(&mut self) {
+		WAITING.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+	}
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Notifier {
+	fn show_clickable(
+		&self,
+		title: String,
+		body: String,
+		video_id: Option<String>,
+	) -> Result<(), KadiumError> {
+		let slot = WaitSlot::take();
+		let mut notification = notify_rust::Notification::new();
+		notification.summary(&title).body(&body).auto_icon();
+		if slot.is_some() {
+			notification.action("default", "Open");
+		}
+		let handle = notification
+			.show()
+			.map_err(|e| KadiumError::other(e.to_string()))?;
+		if let Some(slot) = slot {
+			let notifier = self.clone();
+			// Not a tokio blocking task, because those would delay stopping
+			// the runtime
+			std::thread::spawn(move || {
+				let _slot = slot;
+				handle.wait_for_action(|action| {
+					if action == "default" {
+						notifier.clicked(video_id);
+					}
+				});
+			});
+		}
+		Ok(())
+	}
+}
+
+#[cfg(target_os = "macos")]
+impl Notifier {
+	fn show_clickable(
+		&self,
+		title: String,
+		body: String,
+		video_id: Option<String>,
+	) -> Result<(), KadiumError> {
+		use mac_notification_sys::{Notification, NotificationResponse};
+		use std::sync::Once;
+		use tauri::Manager;
+
+		static SET_APPLICATION: Once = Once::new();
+		SET_APPLICATION.call_once(|| {
+			// Like the notification plugin, which may have set it already
+			let identifier = match tauri::is_dev() {
+				true => "com.apple.Terminal".to_string(),
+				false => self.window.app_handle().config().identifier.clone(),
+			};
+			let _ = mac_notification_sys::set_application(&identifier);
+		});
+
+		let slot = WaitSlot::take();
+		let wait = slot.is_some();
+		let send = move || {
+			Notification::new()
+				.title(&title)
+				.message(&body)
+				.wait_for_click(wait)
+				.send()
+				.map_err(|e| KadiumError::other(e.to_string()))
+		};
+		match slot {
+			Some(slot) => {
+				let notifier = self.clone();
+				// Sending blocks until the notification is clicked or dismissed
+				std::thread::spawn(move || {
+					let _slot = slot;
+					match send() {
+						Ok(NotificationResponse::Click) => notifier.clicked(video_id),
+						Ok(_) => {}
+						Err(e) => eprintln!("Unable to show new video notification: {}", e),
+					}
+				});
+				Ok(())
+			}
+			None => send().map(|_| ()),
+		}
+	}
+}
+
+#[cfg(windows)]
+impl Notifier {
+	fn show_clickable(
+		&self,
+		title: String,
+		body: String,
+		video_id: Option<String>,
+	) -> Result<(), KadiumError> {
+		use tauri::Manager;
+		use tauri_winrt_notification::Toast;
+
+		// Like the notification plugin. The app ID is only registered once
+		// Kadium is installed
+		let app_id = match tauri::is_dev() {
+			true => Toast::POWERSHELL_APP_ID.to_string(),
+			false => self.window.app_handle().config().identifier.clone(),
+		};
+		let notifier = self.clone();
+		Toast::new(&app_id)
+			.title(&title)
+			.text1(&body)
+			.on_activated(move |_action| {
+				notifier.clicked(video_id.clone());
+				Ok(())
+			})
+			.show()
+			.map_err(|e| KadiumError::other(e.to_string()))
+	}
+}
+
+fn video_status(video: &Video) -> String {
+	match video.liveState {
+		LiveState::Upcoming => "Upcoming".to_string(),
+		LiveState::Live => "Live now".to_string(),
+		LiveState::None | LiveState::Replay => format_duration(video.durationMs),
+	}
+}
+
+/// Like 4:05 or 1:02:03
+fn format_duration(ms: i64) -> String {
+	let total_secs = ms / 1000;
+	let hours = total_secs / 3600;
+	let minutes = total_secs / 60 % 60;
+	let secs = total_secs % 60;
+	if hours > 0 {
+		format!("{}:{:02}:{:02}", hours, minutes, secs)
+	} else {
+		format!("{}:{:02}", minutes, secs)
+	}
+}
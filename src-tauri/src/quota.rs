@@ -0,0 +1,123 @@
+use crate::api::Endpoint;
+use crate::data::DataState;
+use crate::error::KadiumError;
+use crate::settings::{Backend, Channel, Settings};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use specta::Type;
+use sqlx::{Row, SqlitePool};
+use tauri::command;
+
+/// The daily quota Google gives new projects
+pub const DAILY_LIMIT: u32 = 10_000;
+
+/// Google resets quotas at midnight Pacific time. US daylight saving time
+/// starts the second Sunday of March and ends the first Sunday of November,
+/// both at 2am local time.
+fn pacific_offset(time: DateTime<Utc>) -> FixedOffset {
+	let year = time.year();
+	let dst_start = nth_sunday(year, 3, 2)
+		.and_hms_opt(10, 0, 0)
+		.unwrap()
+		.and_utc();
+	let dst_end = nth_sunday(year, 11, 1)
+		.and_hms_opt(9, 0, 0)
+		.unwrap()
+		.and_utc();
+	let hours = if time >= dst_start && time < dst_end {
+		-7
+	} else {
+		-8
+	};
+	FixedOffset::east_opt(hours * 60 * 60).unwrap()
+}
+
+fn nth_sunday(year: i32, month: u32, n: u32) -> NaiveDate {
+	let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+	let days_until_sunday = (7 - first.weekday().num_days_from_sunday()) % 7;
+	first + Duration::days((days_until_sunday + 7 * (n - 1)).into())
+}
+
+/// The quota day that `time` falls within, formatted as YYYY-MM-DD
+pub fn quota_day(time: DateTime<Utc>) -> String {
+	let pacific_time = time.with_timezone(&pacific_offset(time));
+	pacific_time.format("%Y-%m-%d").to_string()
+}
+
+/// When the quota day that `time` falls within ends
+pub fn next_reset(time: DateTime<Utc>) -> DateTime<Utc> {
+	let offset = pacific_offset(time);
+	let tomorrow = time.with_timezone(&offset).date_naive() + Duration::days(1);
+	let midnight = tomorrow.and_hms_opt(0, 0, 0).unwrap();
+	match offset.from_local_datetime(&midnight).single() {
+		Some(reset) => reset.with_timezone(&Utc),
+		None => midnight.and_utc(),
+	}
+}
+
+pub async fn add_usage(pool: &SqlitePool, units: u32) -> Result<(), KadiumError> {
+	let query = sqlx::query(
+		"INSERT INTO quotaUsage (day, units) VALUES (?, ?) \
+		ON CONFLICT(day) DO UPDATE SET units = units + excluded.units",
+	)
+	.bind(quota_day(Utc::now()))
+	.bind(units);
+	match query.execute(pool).await {
+		Ok(_) => Ok(()),
+		Err(e) => Err(KadiumError::database("Error saving quota usage", e)),
+	}
+}
+
+pub async fn get_usage(pool: &SqlitePool, day: &str) -> Result<u32, KadiumError> {
+	let query = sqlx::query("SELECT units FROM quotaUsage WHERE day = ?").bind(day);
+	let row = match query.fetch_optional(pool).await {
+		Ok(row) => row,
+		Err(e) => return Err(KadiumError::database("Error getting quota usage", e)),
+	};
+	match row {
+		Some(row) => match row.try_get("units") {
+			Ok(units) => Ok(units),
+			Err(e) => Err(KadiumError::database("Error getting quota usage", e)),
+		},
+		None => Ok(0),
+	}
+}
+
+/// Estimates the quota background checks use per day, assuming each check
+/// needs a single page of uploads and finds no new videos
+pub fn projected_usage(settings: &Settings, channels: &[Channel]) -> u32 {
+	let day_ms = 24 * 60 * 60 * 1000;
+	let mut units = 0;
+	for channel in channels {
+		if channel.backend.unwrap_or(settings.backend) == Backend::Feed {
+			continue; // feeds are free
+		}
+		let checks_per_day = day_ms / channel.refresh_rate_ms.max(1);
+		units += checks_per_day as u32 * Endpoint::PlaylistItems.quota_cost();
+	}
+	units
+}
+
+#[derive(Serialize, Type)]
+pub struct QuotaUsage {
+	/// Units used since the last reset
+	pub used: u32,
+	/// Units background checks are expected to use per day
+	pub projected: u32,
+	pub limit: u32,
+	#[specta(type = i32)] // tauri bigint fix
+	pub resets_at_ms: i64,
+}
+
+#[command]
+#[specta::specta]
+pub async fn get_quota_usage(data: DataState<'_>) -> Result<QuotaUsage, KadiumError> {
+	let data = data.0.lock().await;
+	let now = Utc::now();
+	Ok(QuotaUsage {
+		used: get_usage(&data.db_pool, &quota_day(now)).await?,
+		projected: projected_usage(data.settings_ref(), &data.channels),
+		limit: DAILY_LIMIT,
+		resets_at_ms: next_reset(now).timestamp_millis(),
+	})
+}
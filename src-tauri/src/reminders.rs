@@ -0,0 +1,140 @@
+use crate::error::KadiumError;
+use crate::settings::Channel;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Manager;
+use tauri_plugin_notification::NotificationExt;
+use tokio::task::AbortHandle;
+use tokio::time;
+
+struct Reminder {
+	remind_at_ms: i64,
+	handle: AbortHandle,
+}
+
+/// When each shown reminder was due, by video ID
+#[derive(Clone)]
+pub struct ShownReminders(Arc<Mutex<HashMap<String, i64>>>);
+impl Default for ShownReminders {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl ShownReminders {
+	pub fn new() -> Self {
+		Self(Arc::new(Mutex::new(HashMap::new())))
+	}
+}
+
+/// Notifications before upcoming livestreams and premieres. The desktop
+/// notification plugin can't schedule notifications, so each reminder is a
+/// task on the background runtime, and they stop with it.
+#[derive(Clone)]
+pub struct Reminders {
+	/// How many minutes before the start to remind, by channel ID
+	minutes_before: Arc<HashMap<String, u32>>,
+	scheduled: Arc<Mutex<HashMap<String, Reminder>>>,
+	shown: ShownReminders,
+	window: tauri::WebviewWindow,
+}
+
+impl Reminders {
+	pub fn new(channels: &[Channel], shown: &ShownReminders, window: tauri::WebviewWindow) -> Self {
+		let mut minutes_before = HashMap::new();
+		for channel in channels {
+			if let Some(minutes) = channel.remind_minutes_before {
+				minutes_before.insert(channel.id.clone(), minutes);
+			}
+		}
+		Self {
+			minutes_before: Arc::new(minutes_before),
+			scheduled: Arc::new(Mutex::new(HashMap::new())),
+			shown: shown.clone(),
+			window,
+		}
+	}
+
+	/// Schedules reminders for upcoming videos in the database. Reminders are
+	/// rescheduled if the start time changed, and cancelled if the video is no
+	/// longer upcoming.
+	pub async fn sync(&self, pool: &SqlitePool) -> Result<(), KadiumError> {
+		if self.minutes_before.is_empty() {
+			return Ok(());
+		}
+		let query_str =
+			"SELECT id, title, channelId, channelName, scheduledStartMs FROM videosWithChannel \
+			WHERE liveState = 'Upcoming' AND scheduledStartMs IS NOT NULL";
+		let rows: Vec<(String, String, String, String, i64)> =
+			match sqlx::query_as(query_str).fetch_all(pool).await {
+				Ok(rows) => rows,
+				Err(e) => return Err(KadiumError::database("Unable to get upcoming videos", e)),
+			};
+
+		let now = Utc::now().timestamp_millis();
+		let mut scheduled = self.scheduled.lock().unwrap();
+		let mut shown = self.shown.0.lock().unwrap();
+		let mut upcoming_ids = HashSet::new();
+		for (id, title, channel_id, channel_name, start_ms) in rows {
+			let minutes = match self.minutes_before.get(&channel_id) {
+				Some(minutes) => *minutes,
+				None => continue,
+			};
+			if start_ms <= now {
+				continue; // it's late, and will go live any moment
+			}
+			upcoming_ids.insert(id.clone());
+			let remind_at_ms = start_ms - i64::from(minutes) * 60 * 1000;
+			if shown.get(&id) == Some(&remind_at_ms) {
+				continue;
+			}
+			if let Some(reminder) = scheduled.get(&id) {
+				if reminder.remind_at_ms == remind_at_ms {
+					continue;
+				}
+				reminder.handle.abort();
+			}
+			let delay = Duration::from_millis((remind_at_ms - now).max(0) as u64);
+			let window = self.window.clone();
+			let shown = self.shown.clone();
+			let shown_id = id.clone();
+			let handle = tokio::spawn(async move {
+				time::sleep(delay).await;
+				let minutes_left = (start_ms - Utc::now().timestamp_millis()) / (60 * 1000);
+				let result = window
+					.app_handle()
+					.notification()
+					.builder()
+					.title(format!(
+						"{} starts in {} minutes",
+						channel_name, minutes_left
+					))
+					.body(title)
+					.show();
+				if let Err(e) = result {
+					eprintln!("Unable to show reminder: {}", e);
+				}
+				shown.0.lock().unwrap().insert(shown_id, remind_at_ms);
+			})
+			.abort_handle();
+			scheduled.insert(
+				id,
+				Reminder {
+					remind_at_ms,
+					handle,
+				},
+			);
+		}
+		scheduled.retain(|id, reminder| {
+			let keep = upcoming_ids.contains(id);
+			if !keep {
+				reminder.handle.abort();
+			}
+			keep
+		});
+		shown.retain(|id, _| upcoming_ids.contains(id));
+		Ok(())
+	}
+}
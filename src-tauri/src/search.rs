@@ -0,0 +1,68 @@
+/// A search like `"exact phrase" prefix* channel:name tag:"two words"`
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+	/// FTS5 match expression for titles and descriptions
+	pub text: Option<String>,
+	/// Parts of channel names
+	pub channels: Vec<String>,
+	pub tags: Vec<String>,
+}
+
+/// Splits on whitespace outside of double quotes
+fn tokenize(query: &str) -> Vec<String> {
+	let mut tokens = Vec::new();
+	let mut token = String::new();
+	let mut in_quotes = false;
+	for c in query.chars() {
+		if c == '"' {
+			in_quotes = !in_quotes;
+		} else if c.is_whitespace() && !in_quotes {
+			if !token.is_empty() {
+				tokens.push(std::mem::take(&mut token));
+			}
+			continue;
+		}
+		token.push(c);
+	}
+	if !token.is_empty() {
+		tokens.push(token);
+	}
+	tokens
+}
+
+fn unquote(value: &str) -> String {
+	value.replace('"', "")
+}
+
+/// Quotes a term so FTS5 doesn't interpret its punctuation as syntax. A
+/// trailing `*` is kept outside the quotes to make it a prefix match.
+fn fts_term(token: &str) -> Option<String> {
+	let (term, prefix) = match token.strip_suffix('*') {
+		Some(term) => (term, true),
+		None => (token, false),
+	};
+	let term = unquote(term);
+	if term.trim().is_empty() {
+		return None;
+	}
+	let quoted = format!("\"{}\"", term);
+	Some(if prefix { quoted + "*" } else { quoted })
+}
+
+pub fn parse(query: &str) -> SearchQuery {
+	let mut search = SearchQuery::default();
+	let mut terms: Vec<String> = Vec::new();
+	for token in tokenize(query) {
+		if let Some(channel) = token.strip_prefix("channel:") {
+			search.channels.push(unquote(channel));
+		} else if let Some(tag) = token.strip_prefix("tag:") {
+			search.tags.push(unquote(tag));
+		} else if let Some(term) = fts_term(&token) {
+			terms.push(term);
+		}
+	}
+	if !terms.is_empty() {
+		search.text = Some(terms.join(" "));
+	}
+	search
+}
@@ -1,10 +1,14 @@
 use crate::data::{write_atomically, AppPaths};
-use crate::throw;
-use serde::{Deserialize, Serialize};
+use crate::error::KadiumError;
+use serde::{Deserialize, Deserializer, Serialize};
 use specta::Type;
+use std::collections::BTreeMap;
+use std::env;
 use std::fs::File;
 use std::io::Read;
 
+mod v1;
+
 pub fn default_key() -> String {
 	let key = vec![
 		65, 73, 122, 97, 83, 121, 68, 52, 50, 110, 65, 76, 52, 57, 118, 48, 108, 100, 121, 99, 110,
@@ -13,62 +17,96 @@ pub fn default_key() -> String {
 	String::from_utf8(key).unwrap()
 }
 
+pub const DEFAULT_API_BASE_URL: &str = "https://www.googleapis.com/youtube/v3";
+pub const DEFAULT_FEED_BASE_URL: &str = "https://www.youtube.com/feeds";
+
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "version")]
 pub enum VersionedSettings {
-	V1(Settings),
+	/// Only read, and migrated to V2 by `load`
+	#[serde(skip_serializing)]
+	V1(v1::Settings),
+	V2(Settings),
 }
 impl Default for VersionedSettings {
 	fn default() -> Self {
-		Self::V1(Settings {
+		Self::V2(Settings {
 			api_key: "".to_string(),
 			max_concurrent_requests: 5,
-			channels: Vec::new(),
+			channels_to_import: Vec::new(),
 			check_in_background: true,
 			no_window_decorations: false,
+			max_playlist_pages: default_max_playlist_pages(),
+			backend: Backend::default(),
+			api_base_url: None,
+			feed_base_url: None,
+			connect_timeout_ms: default_connect_timeout_ms(),
+			request_timeout_ms: default_request_timeout_ms(),
+			proxy: None,
+			history_retention: default_history_retention(),
+			tag_archive_rules: BTreeMap::new(),
+			notify_new_uploads: default_notify_new_uploads(),
+			tag_notify_new_uploads: BTreeMap::new(),
+			notification_schedule: NotificationSchedule::default(),
+			notification_click: NotificationClick::default(),
+			archive_on_open: false,
 		})
 	}
 }
 impl VersionedSettings {
 	pub fn unwrap(&mut self) -> &mut Settings {
 		match self {
-			VersionedSettings::V1(user_data) => user_data,
+			VersionedSettings::V2(user_data) => user_data,
+			VersionedSettings::V1(_) => unreachable!("Settings are migrated when loaded"),
 		}
 	}
 	pub fn unwrap_ref(&self) -> &Settings {
 		match self {
-			VersionedSettings::V1(user_data) => user_data,
+			VersionedSettings::V2(user_data) => user_data,
+			VersionedSettings::V1(_) => unreachable!("Settings are migrated when loaded"),
 		}
 	}
-	pub fn load(paths: &AppPaths) -> Result<Self, String> {
+	/// Migrates older versions to the latest one. The old file is backed up
+	/// before it's overwritten
+	pub fn load(paths: &AppPaths) -> Result<Self, KadiumError> {
 		let mut settings_file = match File::open(&paths.settings_file) {
 			Ok(file) => file,
-			Err(e) => throw!("{}", e.to_string()),
+			Err(e) => return Err(KadiumError::file("Error opening settings", e)),
 		};
 		let mut json_str = String::new();
 		match settings_file.read_to_string(&mut json_str) {
 			Ok(_) => {}
-			Err(err) => throw!("Error reading file: {}", err),
+			Err(err) => return Err(KadiumError::file("Error reading settings", err)),
+		};
+		let settings = match serde_json::from_str(&json_str) {
+			Ok(settings) => settings,
+			Err(err) => return Err(KadiumError::file("Error parsing settings", err)),
 		};
-		match serde_json::from_str(&json_str) {
-			Ok(settings) => Ok(settings),
-			Err(err) => {
-				throw!("Error parsing file: {}", err.to_string());
+		match settings {
+			VersionedSettings::V1(v1_settings) => {
+				let backup_file = paths.app_dir.join("Settings.v1.json");
+				if let Err(e) = std::fs::copy(&paths.settings_file, &backup_file) {
+					return Err(KadiumError::file("Error backing up settings", e));
+				}
+				let settings = VersionedSettings::V2(v1_settings.into());
+				settings
+					.save(paths)
+					.map_err(|e| e.context("Error migrating settings"))?;
+				Ok(settings)
 			}
+			VersionedSettings::V2(_) => Ok(settings),
 		}
 	}
-	pub fn save(&self, paths: &AppPaths) -> Result<(), String> {
+	pub fn save(&self, paths: &AppPaths) -> Result<(), KadiumError> {
 		let mut json = Vec::new();
 		let formatter = serde_json::ser::PrettyFormatter::with_indent(b"\t");
 		let mut ser = serde_json::Serializer::with_formatter(&mut json, formatter);
 		match self.serialize(&mut ser) {
 			Ok(_) => {}
-			Err(e) => throw!("Error saving content: {}", e.to_string()),
-		}
-		match write_atomically(&paths.settings_file, &json) {
-			Ok(_) => {}
-			Err(e) => throw!("Error saving: {}", e.to_string()),
+			Err(e) => return Err(KadiumError::file("Error saving settings", e)),
 		}
+		write_atomically(&paths.settings_file, &json)
+			.map_err(|e| e.context("Error saving settings"))?;
 		Ok(())
 	}
 }
@@ -84,21 +122,214 @@ pub struct Channel {
 	#[specta(type = u32)] // tauri bigint fix
 	pub refresh_rate_ms: u64,
 	pub tags: Vec<String>,
+	/// Overrides the global backend
+	pub backend: Option<Backend>,
+	pub archive_rules: ArchiveRules,
+	/// Notify this many minutes before livestreams and premieres start
+	pub remind_minutes_before: Option<u32>,
+	/// Skip Shorts instead of saving them
+	pub ignore_shorts: bool,
+	pub title_filter: TitleFilter,
+	/// Notify about new uploads. Overrides the tags and the global setting
+	pub notify_new_uploads: Option<bool>,
+}
+
+/// History entries keep channels as they were, so they may lack options that
+/// were added since
+pub fn deserialize_old_channel<'de, D: Deserializer<'de>>(
+	deserializer: D,
+) -> Result<Channel, D::Error> {
+	v1::Channel::deserialize(deserializer).map(Into::into)
+}
+pub fn deserialize_old_channels<'de, D: Deserializer<'de>>(
+	deserializer: D,
+) -> Result<Vec<Channel>, D::Error> {
+	let channels = Vec::<v1::Channel>::deserialize(deserializer)?;
+	Ok(channels.into_iter().map(Into::into).collect())
+}
+
+/// Decides which new uploads are saved as usual, based on their title
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Type)]
+pub struct TitleFilter {
+	/// If not empty, titles must match one of these
+	#[serde(default)]
+	pub include: Vec<TitlePattern>,
+	/// Titles matching any of these are filtered out
+	#[serde(default)]
+	pub exclude: Vec<TitlePattern>,
+	/// Save filtered out videos as archived instead of skipping them
+	#[serde(default)]
+	pub archive_filtered: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
+pub struct TitlePattern {
+	pub pattern: String,
+	/// Substrings match case-insensitively. Regexes are case-sensitive unless
+	/// they start with `(?i)`
+	#[serde(default)]
+	pub regex: bool,
+}
+
+/// Videos matching any rule are archived automatically, unless they were unarchived
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Type)]
+pub struct ArchiveRules {
+	/// Archive videos published more than this many days ago
+	#[serde(default)]
+	pub older_than_days: Option<u32>,
+	/// Archive videos shorter than this many seconds
+	#[serde(default)]
+	pub shorter_than_secs: Option<u32>,
+	/// Archive livestreams and premieres once they have ended
+	#[serde(default)]
+	pub live_replays: bool,
+}
+impl ArchiveRules {
+	pub fn is_empty(&self) -> bool {
+		*self == ArchiveRules::default()
+	}
+	/// Combines two sets of rules, keeping the stricter limit of each
+	pub fn merge(&self, other: &ArchiveRules) -> ArchiveRules {
+		fn min(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+			match (a, b) {
+				(Some(a), Some(b)) => Some(a.min(b)),
+				(a, b) => a.or(b),
+			}
+		}
+		fn max(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+			match (a, b) {
+				(Some(a), Some(b)) => Some(a.max(b)),
+				(a, b) => a.or(b),
+			}
+		}
+		ArchiveRules {
+			older_than_days: min(self.older_than_days, other.older_than_days),
+			shorter_than_secs: max(self.shorter_than_secs, other.shorter_than_secs),
+			live_replays: self.live_replays || other.live_replays,
+		}
+	}
+}
+
+/// When new video notifications are held back and delivered as a digest
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Type)]
+pub struct NotificationSchedule {
+	#[serde(default)]
+	pub quiet_hours: Option<QuietHours>,
+	/// Times of day like "08:00". If any are set, new video notifications are
+	/// only delivered at these times
+	#[serde(default)]
+	pub digest_times: Vec<String>,
+}
+
+/// Notifications are queued from `start` until `end`, like "22:00" until
+/// "07:00", and delivered together afterwards
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
+pub struct QuietHours {
+	pub start: String,
+	pub end: String,
+	/// The days the quiet hours start on, like "Mon". Every day if empty
+	#[serde(default)]
+	pub weekdays: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, Type)]
+pub enum NotificationClick {
+	/// Open the video on YouTube
+	OpenVideo,
+	/// Show Kadium with the video selected
+	#[default]
+	ShowApp,
+}
+
+/// Where to look for new uploads
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, Type)]
+pub enum Backend {
+	/// The channel's uploads playlist. Costs quota, but sees every upload
+	#[default]
+	DataApi,
+	/// The channel's Atom feed. Costs no quota, but only sees the latest 15 uploads
+	Feed,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Type)]
 pub struct Settings {
 	api_key: String,
 	pub max_concurrent_requests: u32,
-	pub channels: Vec<Channel>,
+	/// Channels are stored in the database. Older settings files list them
+	/// here, and they're imported once on startup
+	#[serde(default, rename = "channels", skip_serializing_if = "Vec::is_empty")]
+	#[specta(skip)]
+	channels_to_import: Vec<Channel>,
 	pub check_in_background: bool,
-	#[serde(default)]
 	pub no_window_decorations: bool,
+	/// How many pages of 50 uploads to fetch per channel check at most
+	pub max_playlist_pages: u32,
+	pub backend: Backend,
+	/// For pointing Kadium at a caching proxy or mock server
+	pub api_base_url: Option<String>,
+	/// Like `api_base_url`, but for the Atom feeds
+	pub feed_base_url: Option<String>,
+	pub connect_timeout_ms: u32,
+	/// Covers the whole request, including reading the response
+	pub request_timeout_ms: u32,
+	/// HTTP, HTTPS or SOCKS5 proxy URL, like socks5://127.0.0.1:1080
+	pub proxy: Option<String>,
+	/// How many history entries to keep
+	pub history_retention: u32,
+	/// Applies to every channel with the tag
+	pub tag_archive_rules: BTreeMap<String, ArchiveRules>,
+	pub notify_new_uploads: bool,
+	/// Overrides the global setting for every channel with the tag. If the
+	/// channel's tags disagree, notifications are on
+	pub tag_notify_new_uploads: BTreeMap<String, bool>,
+	pub notification_schedule: NotificationSchedule,
+	/// What clicking a new video notification does
+	pub notification_click: NotificationClick,
+	/// Archive videos that are opened from a notification
+	pub archive_on_open: bool,
+}
+fn default_max_playlist_pages() -> u32 {
+	10
+}
+fn default_connect_timeout_ms() -> u32 {
+	10_000
+}
+fn default_request_timeout_ms() -> u32 {
+	30_000
+}
+fn default_history_retention() -> u32 {
+	1000
+}
+fn default_notify_new_uploads() -> bool {
+	true
 }
 impl Settings {
+	/// The channel's own rules combined with the rules of its tags
+	pub fn archive_rules_for(&self, channel: &Channel) -> ArchiveRules {
+		let mut rules = channel.archive_rules.clone();
+		for tag in &channel.tags {
+			if let Some(tag_rules) = self.tag_archive_rules.get(tag) {
+				rules = rules.merge(tag_rules);
+			}
+		}
+		rules
+	}
+	pub fn notify_new_uploads_for(&self, channel: &Channel) -> bool {
+		if let Some(notify) = channel.notify_new_uploads {
+			return notify;
+		}
+		let mut tag_values = channel
+			.tags
+			.iter()
+			.filter_map(|tag| self.tag_notify_new_uploads.get(tag));
+		match tag_values.next() {
+			Some(first) => *first || tag_values.any(|notify| *notify),
+			None => self.notify_new_uploads,
+		}
+	}
 	#[allow(dead_code)]
 	pub fn wrap(self) -> VersionedSettings {
-		VersionedSettings::V1(self)
+		VersionedSettings::V2(self)
 	}
 	pub fn api_key_or_default(&self) -> String {
 		if self.api_key == "" {
@@ -107,7 +338,167 @@ impl Settings {
 			self.api_key.clone()
 		}
 	}
+	/// The `KADIUM_API_BASE_URL` environment variable takes precedence
+	pub fn api_base_url_or_default(&self) -> String {
+		if let Ok(url) = env::var("KADIUM_API_BASE_URL") {
+			return url;
+		}
+		match &self.api_base_url {
+			Some(url) if !url.is_empty() => url.clone(),
+			_ => DEFAULT_API_BASE_URL.to_string(),
+		}
+	}
+	/// The `KADIUM_FEED_BASE_URL` environment variable takes precedence
+	pub fn feed_base_url_or_default(&self) -> String {
+		if let Ok(url) = env::var("KADIUM_FEED_BASE_URL") {
+			return url;
+		}
+		match &self.feed_base_url {
+			Some(url) if !url.is_empty() => url.clone(),
+			_ => DEFAULT_FEED_BASE_URL.to_string(),
+		}
+	}
+	/// Empty once the channels have been imported
+	pub fn take_channels_to_import(&mut self) -> Vec<Channel> {
+		std::mem::take(&mut self.channels_to_import)
+	}
 	pub fn set_api_key(&mut self, key: String) {
 		self.api_key = key;
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+	use std::path::Path;
+
+	fn fixture(name: &str) -> Vec<u8> {
+		let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+			.join("tests/fixtures")
+			.join(name);
+		fs::read(path).unwrap()
+	}
+
+	/// Paths in a new temporary folder, with the fixture as the settings file
+	fn paths_with(name: &str) -> (tempfile::TempDir, AppPaths) {
+		let dir = tempfile::tempdir().unwrap();
+		let paths = AppPaths {
+			app_dir: dir.path().to_path_buf(),
+			settings_file: dir.path().join("Settings.json"),
+			db: String::new(),
+		};
+		fs::write(&paths.settings_file, fixture(name)).unwrap();
+		(dir, paths)
+	}
+
+	/// Loading and saving again must not change the file
+	fn assert_round_trip(paths: &AppPaths) {
+		let before = fs::read(&paths.settings_file).unwrap();
+		VersionedSettings::load(paths).unwrap().save(paths).unwrap();
+		assert_eq!(
+			String::from_utf8(fs::read(&paths.settings_file).unwrap()).unwrap(),
+			String::from_utf8(before).unwrap()
+		);
+	}
+
+	#[test]
+	fn migrates_v1() {
+		let (_dir, paths) = paths_with("settings_v1.json");
+		let mut versioned = VersionedSettings::load(&paths).unwrap();
+		assert_eq!(
+			fs::read(paths.app_dir.join("Settings.v1.json")).unwrap(),
+			fixture("settings_v1.json")
+		);
+		assert_eq!(
+			fs::read(&paths.settings_file).unwrap(),
+			fixture("settings_v2.json")
+		);
+		assert_round_trip(&paths);
+
+		let settings = versioned.unwrap();
+		assert_eq!(settings.max_concurrent_requests, 5);
+		assert_eq!(settings.max_playlist_pages, 10);
+		assert_eq!(settings.backend, Backend::DataApi);
+		assert_eq!(settings.connect_timeout_ms, 10_000);
+		assert_eq!(settings.request_timeout_ms, 30_000);
+		assert_eq!(settings.history_retention, 1000);
+		assert!(settings.notify_new_uploads);
+		assert_eq!(settings.notification_click, NotificationClick::ShowApp);
+
+		let channels = settings.take_channels_to_import();
+		assert_eq!(channels.len(), 2);
+		assert_eq!(channels[0].id, "UC9RM-iSvTu1uPJb8X5yp3EQ");
+		assert_eq!(channels[0].tags, vec!["Planes", "Geography"]);
+		assert_eq!(channels[1].refresh_rate_ms, 600_000);
+		assert_eq!(channels[1].backend, None);
+		assert!(channels[1].archive_rules.is_empty());
+		assert!(!channels[1].ignore_shorts);
+		assert!(settings.take_channels_to_import().is_empty());
+	}
+
+	#[test]
+	fn migrates_v1_with_later_options() {
+		let (_dir, paths) = paths_with("settings_v1_options.json");
+		let mut versioned = VersionedSettings::load(&paths).unwrap();
+		assert_eq!(
+			fs::read(paths.app_dir.join("Settings.v1.json")).unwrap(),
+			fixture("settings_v1_options.json")
+		);
+		assert_round_trip(&paths);
+
+		let settings = versioned.unwrap();
+		assert_eq!(settings.api_key_or_default(), "my key");
+		assert!(!settings.check_in_background);
+		assert!(settings.no_window_decorations);
+		assert_eq!(settings.max_playlist_pages, 2);
+		assert_eq!(settings.backend, Backend::Feed);
+		assert_eq!(
+			settings.feed_base_url.as_deref(),
+			Some("http://127.0.0.1:8080/feeds")
+		);
+		assert_eq!(settings.proxy.as_deref(), Some("socks5://127.0.0.1:1080"));
+		assert_eq!(settings.history_retention, 50);
+		assert_eq!(
+			settings.tag_archive_rules["Planes"].shorter_than_secs,
+			Some(120)
+		);
+		assert!(!settings.notify_new_uploads);
+		assert!(settings.tag_notify_new_uploads["Planes"]);
+		let quiet_hours = settings.notification_schedule.quiet_hours.as_ref().unwrap();
+		assert_eq!(quiet_hours.weekdays, vec!["Mon", "Fri"]);
+		assert_eq!(settings.notification_schedule.digest_times, vec!["08:00"]);
+		assert_eq!(settings.notification_click, NotificationClick::OpenVideo);
+		assert!(settings.archive_on_open);
+
+		let channels = settings.take_channels_to_import();
+		assert_eq!(channels.len(), 1);
+		let channel = &channels[0];
+		assert_eq!(channel.backend, Some(Backend::Feed));
+		assert_eq!(channel.archive_rules.older_than_days, Some(30));
+		assert!(channel.archive_rules.live_replays);
+		assert_eq!(channel.remind_minutes_before, Some(15));
+		assert!(channel.ignore_shorts);
+		assert_eq!(channel.title_filter.exclude[0].pattern, "^#shorts");
+		assert!(channel.title_filter.archive_filtered);
+		assert_eq!(channel.notify_new_uploads, Some(false));
+	}
+
+	#[test]
+	fn loads_v2() {
+		let (_dir, paths) = paths_with("settings_v2.json");
+		assert_round_trip(&paths);
+		assert!(!paths.app_dir.join("Settings.v1.json").exists());
+
+		let mut versioned = VersionedSettings::load(&paths).unwrap();
+		let channels = versioned.unwrap().take_channels_to_import();
+		assert_eq!(channels.len(), 2);
+		// once imported, the channels are left out of the file
+		versioned.save(&paths).unwrap();
+		let saved = String::from_utf8(fs::read(&paths.settings_file).unwrap()).unwrap();
+		assert!(!saved.contains("\"channels\""));
+		assert_round_trip(&paths);
+		let mut versioned = VersionedSettings::load(&paths).unwrap();
+		assert!(versioned.unwrap().take_channels_to_import().is_empty());
+	}
+}
@@ -0,0 +1,115 @@
+//! The settings format before V2. Options were added to it over time, so
+//! every option that isn't in the first version has a default.
+
+use super::{
+	default_connect_timeout_ms, default_history_retention, default_max_playlist_pages,
+	default_notify_new_uploads, default_request_timeout_ms, ArchiveRules, Backend,
+	NotificationClick, NotificationSchedule, TitleFilter,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Deserialize)]
+pub struct Channel {
+	id: String,
+	name: String,
+	icon: String,
+	uploads_playlist_id: String,
+	from_time: i64,
+	refresh_rate_ms: u64,
+	tags: Vec<String>,
+	#[serde(default)]
+	backend: Option<Backend>,
+	#[serde(default)]
+	archive_rules: ArchiveRules,
+	#[serde(default)]
+	remind_minutes_before: Option<u32>,
+	#[serde(default)]
+	ignore_shorts: bool,
+	#[serde(default)]
+	title_filter: TitleFilter,
+	#[serde(default)]
+	notify_new_uploads: Option<bool>,
+}
+impl From<Channel> for super::Channel {
+	fn from(channel: Channel) -> Self {
+		super::Channel {
+			id: channel.id,
+			name: channel.name,
+			icon: channel.icon,
+			uploads_playlist_id: channel.uploads_playlist_id,
+			from_time: channel.from_time,
+			refresh_rate_ms: channel.refresh_rate_ms,
+			tags: channel.tags,
+			backend: channel.backend,
+			archive_rules: channel.archive_rules,
+			remind_minutes_before: channel.remind_minutes_before,
+			ignore_shorts: channel.ignore_shorts,
+			title_filter: channel.title_filter,
+			notify_new_uploads: channel.notify_new_uploads,
+		}
+	}
+}
+
+#[derive(Deserialize)]
+pub struct Settings {
+	api_key: String,
+	max_concurrent_requests: u32,
+	channels: Vec<Channel>,
+	check_in_background: bool,
+	#[serde(default)]
+	no_window_decorations: bool,
+	#[serde(default = "default_max_playlist_pages")]
+	max_playlist_pages: u32,
+	#[serde(default)]
+	backend: Backend,
+	#[serde(default)]
+	api_base_url: Option<String>,
+	#[serde(default)]
+	feed_base_url: Option<String>,
+	#[serde(default = "default_connect_timeout_ms")]
+	connect_timeout_ms: u32,
+	#[serde(default = "default_request_timeout_ms")]
+	request_timeout_ms: u32,
+	#[serde(default)]
+	proxy: Option<String>,
+	#[serde(default = "default_history_retention")]
+	history_retention: u32,
+	#[serde(default)]
+	tag_archive_rules: BTreeMap<String, ArchiveRules>,
+	#[serde(default = "default_notify_new_uploads")]
+	notify_new_uploads: bool,
+	#[serde(default)]
+	tag_notify_new_uploads: BTreeMap<String, bool>,
+	#[serde(default)]
+	notification_schedule: NotificationSchedule,
+	#[serde(default)]
+	notification_click: NotificationClick,
+	#[serde(default)]
+	archive_on_open: bool,
+}
+impl From<Settings> for super::Settings {
+	fn from(settings: Settings) -> Self {
+		super::Settings {
+			api_key: settings.api_key,
+			max_concurrent_requests: settings.max_concurrent_requests,
+			channels_to_import: settings.channels.into_iter().map(Into::into).collect(),
+			check_in_background: settings.check_in_background,
+			no_window_decorations: settings.no_window_decorations,
+			max_playlist_pages: settings.max_playlist_pages,
+			backend: settings.backend,
+			api_base_url: settings.api_base_url,
+			feed_base_url: settings.feed_base_url,
+			connect_timeout_ms: settings.connect_timeout_ms,
+			request_timeout_ms: settings.request_timeout_ms,
+			proxy: settings.proxy,
+			history_retention: settings.history_retention,
+			tag_archive_rules: settings.tag_archive_rules,
+			notify_new_uploads: settings.notify_new_uploads,
+			tag_notify_new_uploads: settings.tag_notify_new_uploads,
+			notification_schedule: settings.notification_schedule,
+			notification_click: settings.notification_click,
+			archive_on_open: settings.archive_on_open,
+		}
+	}
+}
@@ -0,0 +1,61 @@
+use crate::error::KadiumError;
+use crate::settings::{TitleFilter, TitlePattern};
+use regex::Regex;
+
+enum Matcher {
+	/// Lowercased, for matching case-insensitively
+	Substring(String),
+	Regex(Regex),
+}
+impl Matcher {
+	fn new(pattern: &TitlePattern) -> Result<Self, KadiumError> {
+		if pattern.regex {
+			match Regex::new(&pattern.pattern) {
+				Ok(regex) => Ok(Matcher::Regex(regex)),
+				Err(e) => Err(KadiumError::other(e.to_string())
+					.context(format!("Invalid regex {}", pattern.pattern))),
+			}
+		} else {
+			Ok(Matcher::Substring(pattern.pattern.to_lowercase()))
+		}
+	}
+	fn is_match(&self, title: &str, title_lowercase: &str) -> bool {
+		match self {
+			Matcher::Substring(substring) => title_lowercase.contains(substring.as_str()),
+			Matcher::Regex(regex) => regex.is_match(title),
+		}
+	}
+}
+
+/// A compiled `TitleFilter`. The default lets every title through
+#[derive(Default)]
+pub struct TitleMatcher {
+	include: Vec<Matcher>,
+	exclude: Vec<Matcher>,
+	pub archive_filtered: bool,
+}
+impl TitleMatcher {
+	pub fn new(filter: &TitleFilter) -> Result<Self, KadiumError> {
+		let compile = |patterns: &[TitlePattern]| -> Result<Vec<Matcher>, KadiumError> {
+			patterns.iter().map(Matcher::new).collect()
+		};
+		Ok(TitleMatcher {
+			include: compile(&filter.include)?,
+			exclude: compile(&filter.exclude)?,
+			archive_filtered: filter.archive_filtered,
+		})
+	}
+	pub fn allows(&self, title: &str) -> bool {
+		let title_lowercase = title.to_lowercase();
+		let included = self.include.is_empty()
+			|| self
+				.include
+				.iter()
+				.any(|matcher| matcher.is_match(title, &title_lowercase));
+		included
+			&& !self
+				.exclude
+				.iter()
+				.any(|matcher| matcher.is_match(title, &title_lowercase))
+	}
+}
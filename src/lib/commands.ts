@@ -1,5 +1,17 @@
 // utils.ts
-import { commands } from '../../bindings'
+import { commands, type KadiumError } from '../../bindings'
+
+export class CommandError extends Error {
+	kind: KadiumError['kind']
+	constructor(error: KadiumError) {
+		super(formatError(error))
+		this.kind = error.kind
+	}
+}
+
+export function formatError(error: KadiumError) {
+	return error.context ? `${error.context}: ${error.message}` : error.message
+}
 
 export default new Proxy({} as typeof commands, {
 	get:
@@ -9,7 +21,7 @@ export default new Proxy({} as typeof commands, {
 				// eslint-disable-next-line @typescript-eslint/no-explicit-any
 				const result = await (commands as any)[property](...args)
 				if (result && 'status' in result && result.status === 'error') {
-					throw new Error(result.error)
+					throw new CommandError(result.error)
 				}
 
 				return result
@@ -8,6 +8,7 @@ export type ViewOptions = {
 	show_archived: boolean
 	channel_filter: string
 	tag: string | null
+	query: string
 	limit: number
 }
 export const viewOptions: Writable<ViewOptions> = writable({
@@ -15,6 +16,7 @@ export const viewOptions: Writable<ViewOptions> = writable({
 	show_archived: false,
 	channel_filter: '',
 	tag: null,
+	query: '',
 	limit: 100,
 })
 
@@ -22,8 +24,15 @@ export const videos: Writable<Video[]> = writable([])
 
 export const settingsOpen = writable(false)
 export const settings: Writable<null | Settings> = writable(null)
+export const channels: Writable<Channel[]> = writable([])
 export const tags: Writable<string[]> = writable([])
 export async function loadSettings() {
+	// before settings, so the channels are there once settings are shown
+	const channelsResult = await commands.getChannels()
+	if (channelsResult.status === 'ok') {
+		channels.set(channelsResult.data)
+	}
+
 	const settingsResult = await commands.getSettings()
 	if (settingsResult.status === 'ok') {
 		settings.set(settingsResult.data)
@@ -52,15 +61,16 @@ export function enableSampleData() {
 					thumbnailStandard: true,
 					title: 'Why Electric Planes are Inevitably Coming',
 					unread: true,
+					liveState: 'None',
+					scheduledStartMs: null,
+					isShort: false,
 				})
 			}
 			return videos
 		})(),
 	)
-	settings.set({
-		api_key: 'example key',
-		max_concurrent_requests: 5,
-		channels: (() => {
+	channels.set(
+		(() => {
 			const channels: Channel[] = []
 			for (let i = 0; i < 100; i++) {
 				channels.push({
@@ -71,11 +81,35 @@ export function enableSampleData() {
 					uploads_playlist_id: 'UUp4csaOD64mSzPxbfuzJcuA',
 					refresh_rate_ms: 60 * 1000,
 					tags: ['Chungus'],
+					backend: null,
+					archive_rules: {},
+					remind_minutes_before: null,
+					ignore_shorts: false,
+					title_filter: {},
+					notify_new_uploads: null,
 				})
 			}
 			return channels
 		})(),
+	)
+	settings.set({
+		api_key: 'example key',
+		max_concurrent_requests: 5,
 		check_in_background: true,
 		no_window_decorations: true,
+		max_playlist_pages: 10,
+		backend: 'DataApi',
+		api_base_url: null,
+		feed_base_url: null,
+		connect_timeout_ms: 10000,
+		request_timeout_ms: 30000,
+		proxy: null,
+		history_retention: 1000,
+		tag_archive_rules: {},
+		notify_new_uploads: true,
+		tag_notify_new_uploads: {},
+		notification_schedule: {},
+		notification_click: 'ShowApp',
+		archive_on_open: false,
 	})
 }